@@ -0,0 +1,78 @@
+//! Stack unwinding for diagnostics: walks the saved frame-pointer (`s0`/`fp`) chain to recover
+//! return addresses without DWARF unwind tables or a debugger attached. Used by the panic
+//! handler and by `Thread::handle_user_trap`'s `TrapReason::Unknown` branch, so a fault that
+//! currently only gets a one-line warning also gets a call path to go with it.
+//!
+//! This relies on the standard RISC-V frame layout emitted by a `-fno-omit-frame-pointer`
+//! prologue: relative to a frame's own frame pointer `fp`, the caller's return address lives at
+//! `fp - 8` and the caller's frame pointer at `fp - 16`. Walking it is just following that chain
+//! until `fp` goes null, misaligned, or non-increasing.
+
+use alloc::vec::Vec;
+
+/// Backstop against a corrupted or cyclic frame-pointer chain: stop after this many frames
+/// even if every one of them looks plausible.
+const MAX_FRAMES: usize = 64;
+
+/// The outermost frame's saved return address has been observed to read back all-ones (i.e.
+/// `-1` sign-extended to `usize`) rather than a real PC, when unwinding starts from a leaf that
+/// hasn't finished setting up its own frame yet. Only ever seen on the first frame; treat it as
+/// "no return address here" and keep walking instead of printing it as a PC.
+const CORRUPT_RA_SENTINEL: usize = usize::MAX;
+
+/// Read the current frame pointer (`s0`).
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe { asm!("mv {0}, s0", out(reg) fp) };
+    fp
+}
+
+/// Walk the frame-pointer chain starting at the caller of `backtrace()`, returning each frame's
+/// return address, innermost (closest to the call site) first.
+pub fn backtrace() -> Vec<usize> {
+    let mut pcs = Vec::new();
+    let mut fp = frame_pointer();
+    for i in 0..MAX_FRAMES {
+        if fp == 0 || fp % core::mem::size_of::<usize>() != 0 {
+            break;
+        }
+        let ra = unsafe { *((fp - 8) as *const usize) };
+        let next_fp = unsafe { *((fp - 16) as *const usize) };
+        if ra != 0 && ra != CORRUPT_RA_SENTINEL {
+            pcs.push(ra);
+        } else if i != 0 {
+            // A non-leading frame reading back all-ones means the chain has run off the end
+            // of the stack, not just a known first-frame quirk -- stop instead of looping.
+            break;
+        }
+        if next_fp <= fp {
+            break;
+        }
+        fp = next_fp;
+    }
+    pcs
+}
+
+/// Print `backtrace()` via `error!`, one frame per line. Called from the panic handler and from
+/// `TrapReason::Unknown` handling so a fault leaves more than a single PC behind.
+pub fn dump_backtrace() {
+    error!("backtrace:");
+    for (i, pc) in backtrace().into_iter().enumerate() {
+        error!("  #{:02} {:#018x}", i, pc);
+    }
+}
+
+/// Function-entry tracing for hot paths, compiled in only under the `trace-calls` feature (not
+/// declared in a `Cargo.toml` in this tree -- add it as a default-off feature alongside the rest
+/// of the crate's dependencies when building for real). Not a true `#[trace_callback]` attribute
+/// macro, since doing that properly needs a proc-macro crate this workspace doesn't have; call
+/// it as the first statement of a function instead. Logs via `trace!`, so it also needs
+/// `RUST_LOG`/the logger's level set to see anything even with the feature on.
+#[macro_export]
+macro_rules! trace_call {
+    ($label:expr) => {
+        #[cfg(feature = "trace-calls")]
+        trace!("call: {} (cpu {})", $label, $crate::arch::cpu::id());
+    };
+}
@@ -41,6 +41,10 @@ impl ThreadContext for ArchThreadContext {
         self.user.set_tls(tls)
     }
 
+    fn fork(&self) -> Self {
+        Self { user: self.user }
+    }
+
     fn run(&mut self) -> TrapReason {
         self.user.run();
         let scause = scause::read();
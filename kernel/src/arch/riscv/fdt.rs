@@ -0,0 +1,151 @@
+//! A minimal flattened-device-tree (FDT/DTB) walker — just enough to discover RAM banks and
+//! reserved ranges for `memory::discover_regions`, not a general-purpose device tree library.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::memory::addr::phys_to_virt;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+unsafe fn be32(addr: usize) -> u32 {
+    u32::from_be((addr as *const u32).read_unaligned())
+}
+
+unsafe fn be64(addr: usize) -> u64 {
+    u64::from_be((addr as *const u64).read_unaligned())
+}
+
+unsafe fn cstr(addr: usize) -> &'static str {
+    let mut len = 0;
+    while *(addr as *const u8).add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8_unchecked(core::slice::from_raw_parts(addr as *const u8, len))
+}
+
+fn align4(off: usize) -> usize {
+    (off + 3) & !3
+}
+
+/// Cell sizes (in 32-bit words) that govern how a node's own `reg` property is parsed, and how
+/// its children's `reg` properties are parsed, per the device tree spec: `#address-cells`/
+/// `#size-cells` set *in* a node apply to that node's *children*, not to the node itself.
+struct Frame<'a> {
+    name: &'a str,
+    reg_cells: (u32, u32),
+    child_cells: (u32, u32),
+}
+
+/// Read one `reg` entry's `(address, size)` pair, `cells.0`/`cells.1` 32-bit words each, starting
+/// at `addr`; returns the pair and the address immediately after it.
+unsafe fn read_reg_entry(addr: usize, cells: (u32, u32)) -> ((u64, u64), usize) {
+    let read_cells = |mut addr: usize, n: u32| -> (u64, usize) {
+        let mut v = 0u64;
+        for _ in 0..n {
+            v = (v << 32) | be32(addr) as u64;
+            addr += 4;
+        }
+        (v, addr)
+    };
+    let (base, addr) = read_cells(addr, cells.0);
+    let (size, addr) = read_cells(addr, cells.1);
+    ((base, size), addr)
+}
+
+/// Parse the FDT at `dtb_paddr`, returning `(ram_regions, reserved_regions)` — the `reg` of every
+/// `/memory` node, the `reg` of every node under `/reserved-memory`, and the header's
+/// memory-reservation block. Returns `None` if `dtb_paddr` doesn't point at a valid FDT (no magic
+/// match), so the caller can fall back to a default memory map.
+pub fn scan(dtb_paddr: usize) -> Option<(Vec<Range<usize>>, Vec<Range<usize>>)> {
+    let base = phys_to_virt(dtb_paddr);
+    if unsafe { be32(base) } != FDT_MAGIC {
+        return None;
+    }
+    let off_dt_struct = unsafe { be32(base + 8) } as usize;
+    let off_dt_strings = unsafe { be32(base + 12) } as usize;
+    let off_mem_rsvmap = unsafe { be32(base + 16) } as usize;
+
+    let mut reserved = Vec::new();
+    let mut off = off_mem_rsvmap;
+    loop {
+        let (addr, size) = unsafe { (be64(base + off), be64(base + off + 8)) };
+        if size == 0 {
+            break;
+        }
+        reserved.push(addr as usize..(addr + size) as usize);
+        off += 16;
+    }
+
+    let mut ram = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut pos = off_dt_struct;
+    loop {
+        let token = unsafe { be32(base + pos) };
+        pos += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = unsafe { cstr(base + pos) };
+                pos = align4(pos + name.len() + 1);
+                let parent_child_cells = stack.last().map_or((2, 2), |f| f.child_cells);
+                stack.push(Frame {
+                    name,
+                    reg_cells: parent_child_cells,
+                    child_cells: (2, 2),
+                });
+            }
+            FDT_END_NODE => {
+                stack.pop();
+            }
+            FDT_PROP => {
+                let len = unsafe { be32(base + pos) } as usize;
+                let nameoff = unsafe { be32(base + pos + 4) } as usize;
+                let data = pos + 8;
+                pos = align4(data + len);
+                let prop_name = unsafe { cstr(base + off_dt_strings + nameoff) };
+                let frame = match stack.last_mut() {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+                match prop_name {
+                    "#address-cells" if len == 4 => {
+                        frame.child_cells.0 = unsafe { be32(base + data) }
+                    }
+                    "#size-cells" if len == 4 => {
+                        frame.child_cells.1 = unsafe { be32(base + data) }
+                    }
+                    "reg" => {
+                        let is_memory = frame.name == "memory" || frame.name.starts_with("memory@");
+                        let is_reserved = stack.iter().any(|f| f.name == "reserved-memory");
+                        if is_memory || is_reserved {
+                            let cells = stack.last().unwrap().reg_cells;
+                            let mut entry = base + data;
+                            let end = base + data + len;
+                            while entry < end {
+                                let ((addr, size), next) =
+                                    unsafe { read_reg_entry(entry, cells) };
+                                let region = addr as usize..(addr + size) as usize;
+                                if is_memory {
+                                    ram.push(region);
+                                } else {
+                                    reserved.push(region);
+                                }
+                                entry = next;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => break,
+            _ => break,
+        }
+    }
+    Some((ram, reserved))
+}
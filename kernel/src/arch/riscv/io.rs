@@ -27,3 +27,12 @@ pub fn putfmt(fmt: Arguments) {
     static CONSOLE: Mutex<Console> = Mutex::new(Console);
     CONSOLE.lock().write_fmt(fmt).unwrap();
 }
+
+/// Poll for one byte of console input. `None` if nothing is waiting yet -- there's no input
+/// interrupt wired up, only `sbi::console_getchar`'s synchronous poll.
+pub fn getchar() -> Option<u8> {
+    match super::sbi::console_getchar() {
+        usize::MAX => None,
+        ch => Some(ch as u8),
+    }
+}
@@ -2,7 +2,9 @@ use alloc::vec::Vec;
 use core::ops::Range;
 
 use riscv::register::sstatus;
+use spin::Mutex;
 
+use super::fdt;
 use crate::error::AcoreResult;
 use crate::memory::{
     addr::{align_up, virt_to_phys},
@@ -24,17 +26,73 @@ pub mod consts {
 
     pub const DEVICE_START: usize = 0x9000_0000;
     pub const DEVICE_END: usize = 0x9800_0000;
+
+    /// Frequency of the `time` CSR on QEMU's RISC-V `virt` machine, in Hz.
+    pub const CLOCK_FREQ: usize = 10_000_000;
 }
 
-pub type FrameAlloc = bitmap_allocator::BitAlloc1M;
+/// Free physical regions discovered by `init_fdt`, consulted by `get_phys_memory_regions` in
+/// place of the `PHYS_MEMORY_OFFSET..PHYS_MEMORY_END` compile-time range. `None` until
+/// `init_fdt` runs during `arch::primary_init_early`.
+static FREE_REGIONS: Mutex<Option<Vec<Range<usize>>>> = Mutex::new(None);
 
-pub fn get_phys_memory_regions() -> Vec<Range<usize>> {
+/// Parse the FDT at `dtb_paddr` to discover RAM banks (`/memory` nodes) and carve out
+/// `/reserved-memory` nodes, the DTB's own memory-reservation block, and the kernel image itself,
+/// so `frame::init()` hands out only genuinely free frames instead of a compile-time range. Falls
+/// back to the old compile-time range if `dtb_paddr` doesn't point at a valid FDT.
+pub fn init_fdt(dtb_paddr: usize) {
     extern "C" {
         fn kernel_end();
     }
-    let start = align_up(virt_to_phys(kernel_end as usize));
-    let end = consts::PHYS_MEMORY_END;
-    vec![start..end]
+    let kernel_end = align_up(virt_to_phys(kernel_end as usize));
+
+    let (ram, mut reserved) = match fdt::scan(dtb_paddr) {
+        Some((ram, reserved)) if !ram.is_empty() => (ram, reserved),
+        _ => {
+            warn!(
+                "no usable FDT at {:#x}, falling back to the default memory map",
+                dtb_paddr
+            );
+            (
+                vec![consts::PHYS_MEMORY_OFFSET..consts::PHYS_MEMORY_END],
+                Vec::new(),
+            )
+        }
+    };
+    // The kernel image (and everything below it: firmware, the DTB blob itself) is never free.
+    reserved.push(consts::PHYS_MEMORY_OFFSET..kernel_end);
+
+    *FREE_REGIONS.lock() = Some(subtract_regions(ram, reserved));
+}
+
+/// `ram` minus every range in `reserved`, as a sorted, disjoint region list.
+fn subtract_regions(ram: Vec<Range<usize>>, mut reserved: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    reserved.sort_by_key(|r| r.start);
+    let mut free = Vec::new();
+    for region in ram {
+        let mut cursor = region.start;
+        for res in &reserved {
+            let lo = res.start.max(cursor);
+            let hi = res.end.min(region.end);
+            if lo < hi {
+                if cursor < lo {
+                    free.push(cursor..lo);
+                }
+                cursor = cursor.max(hi);
+            }
+        }
+        if cursor < region.end {
+            free.push(cursor..region.end);
+        }
+    }
+    free
+}
+
+pub fn get_phys_memory_regions() -> Vec<Range<usize>> {
+    FREE_REGIONS
+        .lock()
+        .clone()
+        .expect("init_fdt() must run before the frame allocator is initialized")
 }
 
 pub fn create_mapping(ms: &mut MemorySet) -> AcoreResult {
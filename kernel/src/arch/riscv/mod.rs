@@ -1,15 +1,26 @@
+mod backtrace;
 pub mod context;
 pub mod cpu;
+mod fdt;
 pub mod io;
 pub mod memory;
 mod paging;
 mod sbi;
+pub mod timer;
 mod traps;
 
+pub use backtrace::{backtrace, dump_backtrace};
+
 global_asm!(include_str!("boot/entry.S"));
 
-pub fn primary_init_early(_hartid: usize, _device_tree_paddr: usize) {}
+pub fn primary_init_early(_hartid: usize, device_tree_paddr: usize) {
+    memory::init_fdt(device_tree_paddr);
+}
 
-pub fn primary_init(_hartid: usize, _device_tree_paddr: usize) {}
+pub fn primary_init(_hartid: usize, _device_tree_paddr: usize) {
+    timer::init();
+}
 
-pub fn secondary_init(_hartid: usize, _device_tree_paddr: usize) {}
+pub fn secondary_init(_hartid: usize, _device_tree_paddr: usize) {
+    timer::init();
+}
@@ -1,5 +1,9 @@
 use alloc::vec::Vec;
-use core::{convert::From, mem::ManuallyDrop};
+use core::{
+    convert::From,
+    mem::ManuallyDrop,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use riscv::asm::{sfence_vma, sfence_vma_all};
 use riscv::paging::{
@@ -7,9 +11,10 @@ use riscv::paging::{
 };
 use riscv::register::satp;
 
+use super::sbi;
 use crate::arch::memory::PHYS_VIRT_OFFSET;
 use crate::error::{AcoreError, AcoreResult};
-use crate::memory::{addr::phys_to_virt, Frame, PhysAddr, VirtAddr};
+use crate::memory::{addr::phys_to_virt, Frame, PhysAddr, VirtAddr, PAGE_SIZE};
 use crate::memory::{MMUFlags, PageTable, PageTableEntry};
 
 mod rv {
@@ -24,6 +29,9 @@ pub struct RvPageTable {
     inner: TopLevelPageTable<'static>,
     root: Frame,
     allocator: PageTableFrameAllocator,
+    /// Bitmask of harts this page table has been activated on (see `mark_active_hart`), so
+    /// `flush_tlb`/`flush_tlb_range` know which other harts to shoot down via SBI.
+    active_harts: AtomicUsize,
 }
 
 impl From<MMUFlags> for PTF {
@@ -41,6 +49,12 @@ impl From<MMUFlags> for PTF {
         if f.contains(MMUFlags::USER) {
             ret |= PTF::USER;
         }
+        if f.contains(MMUFlags::ACCESSED) {
+            ret |= PTF::ACCESSED;
+        }
+        if f.contains(MMUFlags::DIRTY) {
+            ret |= PTF::DIRTY;
+        }
         ret
     }
 }
@@ -60,6 +74,12 @@ impl From<PTF> for MMUFlags {
         if f.contains(PTF::USER) {
             ret |= MMUFlags::USER;
         }
+        if f.contains(PTF::ACCESSED) {
+            ret |= MMUFlags::ACCESSED;
+        }
+        if f.contains(PTF::DIRTY) {
+            ret |= MMUFlags::DIRTY;
+        }
         ret
     }
 }
@@ -74,6 +94,12 @@ impl PageTableEntry for PTE {
     fn is_present(&self) -> bool {
         self.flags().contains(PTF::VALID)
     }
+    fn accessed(&self) -> bool {
+        self.flags().contains(PTF::ACCESSED)
+    }
+    fn dirty(&self) -> bool {
+        self.flags().contains(PTF::DIRTY)
+    }
     fn set_addr(&mut self, paddr: PhysAddr) {
         let frame = rv::Frame::of_addr(rv::PhysAddr::new(paddr));
         self.set(frame, self.flags())
@@ -81,6 +107,14 @@ impl PageTableEntry for PTE {
     fn set_flags(&mut self, flags: MMUFlags) {
         self.set(self.frame(), flags.into())
     }
+    fn clear_accessed(&mut self) {
+        let flags = self.flags() - PTF::ACCESSED;
+        self.set(self.frame(), flags)
+    }
+    fn clear_dirty(&mut self) {
+        let flags = self.flags() - PTF::DIRTY;
+        self.set(self.frame(), flags)
+    }
     fn clear(&mut self) {
         self.set_unused()
     }
@@ -119,6 +153,7 @@ impl PageTable for RvPageTable {
             inner: TopLevelPageTable::new(table, PHYS_VIRT_OFFSET),
             root,
             allocator: PageTableFrameAllocator::new(),
+            active_harts: AtomicUsize::new(0),
         }
     }
 
@@ -128,6 +163,7 @@ impl PageTable for RvPageTable {
             inner: TopLevelPageTable::new(table, PHYS_VIRT_OFFSET),
             root: ManuallyDrop::into_inner(Frame::from_paddr(root_paddr)),
             allocator: PageTableFrameAllocator::new(),
+            active_harts: AtomicUsize::new(0),
         })
     }
 
@@ -139,7 +175,7 @@ impl PageTable for RvPageTable {
         satp::set(satp::Mode::Sv39, 0, root_paddr >> 12)
     }
 
-    fn flush_tlb(vaddr: Option<VirtAddr>) {
+    fn flush_tlb(&self, vaddr: Option<VirtAddr>) {
         unsafe {
             if let Some(vaddr) = vaddr {
                 sfence_vma(0, vaddr)
@@ -147,6 +183,29 @@ impl PageTable for RvPageTable {
                 sfence_vma_all()
             }
         }
+        let remote_mask = self.remote_harts();
+        if remote_mask != 0 {
+            match vaddr {
+                Some(vaddr) => sbi::remote_sfence_vma(remote_mask, vaddr, PAGE_SIZE),
+                None => sbi::remote_sfence_vma(remote_mask, 0, usize::MAX),
+            }
+        }
+    }
+
+    fn flush_tlb_range(&self, start: VirtAddr, end: VirtAddr) {
+        unsafe { sfence_vma_all() }
+        let remote_mask = self.remote_harts();
+        if remote_mask != 0 {
+            sbi::remote_sfence_vma(remote_mask, start, end - start);
+        }
+    }
+
+    fn mark_active_hart(&self, hart_id: usize) {
+        self.active_harts.fetch_or(1 << hart_id, Ordering::SeqCst);
+    }
+
+    fn active_harts(&self) -> usize {
+        self.active_harts.load(Ordering::SeqCst)
     }
 
     fn root_paddr(&self) -> PhysAddr {
@@ -188,6 +247,35 @@ impl PageTable for RvPageTable {
     }
 }
 
+impl RvPageTable {
+    /// Harts other than the caller that have this page table active, and so need an SBI
+    /// remote fence in addition to our own local `sfence.vma`.
+    fn remote_harts(&self) -> usize {
+        self.active_harts() & !(1 << crate::arch::cpu::id())
+    }
+
+    /// Walk every present page in `[start, end)`, recording its accessed/dirty bits and
+    /// clearing both so the next scan only sees activity since now. `memory::reclaim::Clock`
+    /// does not go through this: it reads and clears each tracked PTE's bits itself.
+    pub fn scan_and_clear_ad_bits(
+        &mut self,
+        start: VirtAddr,
+        end: VirtAddr,
+    ) -> Vec<(VirtAddr, bool, bool)> {
+        let mut result = Vec::new();
+        for vaddr in (start..end).step_by(PAGE_SIZE) {
+            if let Ok(entry) = self.get_entry(vaddr) {
+                if entry.is_present() {
+                    result.push((vaddr, entry.accessed(), entry.dirty()));
+                    entry.clear_accessed();
+                    entry.clear_dirty();
+                }
+            }
+        }
+        result
+    }
+}
+
 struct PageTableFrameAllocator {
     frames: Vec<Frame>,
 }
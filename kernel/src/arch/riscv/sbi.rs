@@ -13,12 +13,18 @@ const SBI_SHUTDOWN: usize = 8;
 
 #[inline(always)]
 fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
+    sbi_call4(which, arg0, arg1, arg2, 0)
+}
+
+#[inline(always)]
+fn sbi_call4(which: usize, arg0: usize, arg1: usize, arg2: usize, arg3: usize) -> usize {
     let ret;
     unsafe {
         asm!("ecall",
             in("a0") arg0,
             in("a1") arg1,
             in("a2") arg2,
+            in("a3") arg3,
             in("a7") which,
             lateout("a0") ret,
         );
@@ -63,15 +69,21 @@ pub fn remote_fence_i(hart_mask: usize) {
     sbi_call(SBI_REMOTE_FENCE_I, &hart_mask as *const _ as usize, 0, 0);
 }
 
-pub fn remote_sfence_vma(hart_mask: usize, _start: usize, _size: usize) {
-    sbi_call(SBI_REMOTE_SFENCE_VMA, &hart_mask as *const _ as usize, 0, 0);
+pub fn remote_sfence_vma(hart_mask: usize, start: usize, size: usize) {
+    sbi_call(
+        SBI_REMOTE_SFENCE_VMA,
+        &hart_mask as *const _ as usize,
+        start,
+        size,
+    );
 }
 
-pub fn remote_sfence_vma_asid(hart_mask: usize, _start: usize, _size: usize, _asid: usize) {
-    sbi_call(
+pub fn remote_sfence_vma_asid(hart_mask: usize, start: usize, size: usize, asid: usize) {
+    sbi_call4(
         SBI_REMOTE_SFENCE_VMA_ASID,
         &hart_mask as *const _ as usize,
-        0,
-        0,
+        start,
+        size,
+        asid,
     );
 }
@@ -0,0 +1,63 @@
+//! Timer-driven preemptive scheduling.
+//!
+//! Programs the SBI timer to fire a `SupervisorTimer` trap every quantum. Each tick charges the
+//! currently running thread one quantum of its time slice; once the slice is used up the thread
+//! is marked as needing a reschedule, so `Thread::run_user`'s loop yields at the next trap
+//! boundary instead of waiting for the thread to call `yield_now()` itself.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use riscv::register::{sie, time};
+
+use super::memory::consts::CLOCK_FREQ;
+use super::sbi;
+use crate::config::TIMER_QUANTUM_MS;
+use crate::task::PerCpu;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Nanoseconds per timer quantum (`TIMER_QUANTUM_MS`) — the finest granularity `tick_count()`,
+/// and so `sched::Timer`, can actually resolve a sleep to.
+const QUANTUM_NANOS: u64 = TIMER_QUANTUM_MS as u64 * 1_000_000;
+
+/// Number of timer ticks taken since boot: one shared counter, bumped by every CPU's `tick()`.
+pub fn tick_count() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Convert a duration in nanoseconds to the number of timer quanta it takes at least that long
+/// to elapse, rounding up so a sleep never wakes early. `0` nanoseconds (or anything that's
+/// already elapsed, computed by the caller via a saturating subtraction) rounds down to `0`
+/// quanta, letting `sys_nanosleep` skip parking the thread entirely.
+pub fn nanos_to_ticks(nanos: u64) -> u64 {
+    (nanos + QUANTUM_NANOS - 1) / QUANTUM_NANOS
+}
+
+/// Read the `time` CSR, a monotonic counter incrementing at `CLOCK_FREQ` Hz.
+pub fn now() -> u64 {
+    time::read() as u64
+}
+
+/// Program the next timer interrupt one quantum from now.
+pub fn set_next_trigger() {
+    sbi::set_timer(now() + CLOCK_FREQ as u64 / 1000 * TIMER_QUANTUM_MS as u64);
+}
+
+/// Arm the timer interrupt and schedule the first tick. Called once per CPU during init.
+pub fn init() {
+    unsafe { sie::set_stimer() };
+    set_next_trigger();
+}
+
+/// Handle a `SupervisorTimer` trap: advance the tick count, reprogram the next tick, drive the
+/// timing wheel, and charge the thread currently running on this CPU one tick of its time
+/// slice (see `Thread::charge_time_slice`), preempting it once the slice runs out so a
+/// compute-bound user thread cannot monopolize this hart.
+pub fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    set_next_trigger();
+    crate::sched::on_tick();
+    if let Some(thread) = PerCpu::from_current_cpu_id().thread() {
+        thread.charge_time_slice();
+    }
+}
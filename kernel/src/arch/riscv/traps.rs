@@ -18,7 +18,7 @@ extern "C" fn trap_handler(_tf: &mut TrapFrame) {
     match scause.cause() {
         Trap::Interrupt(I::SupervisorExternal) => {}
         Trap::Interrupt(I::SupervisorSoft) => ipi(),
-        Trap::Interrupt(I::SupervisorTimer) => {}
+        Trap::Interrupt(I::SupervisorTimer) => super::timer::tick(),
         Trap::Exception(E::InstructionPageFault) => {
             handle_page_fault(stval, MMUFlags::EXECUTE).unwrap()
         }
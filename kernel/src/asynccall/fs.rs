@@ -1,8 +1,14 @@
 use super::{AsyncCall, AsyncCallResult};
 use crate::error::AcoreError;
 use crate::memory::uaccess::{UserInPtr, UserOutPtr};
+use crate::sched::yield_now;
 
 impl AsyncCall {
+    /// Reads in place into the user buffer via a zero-copy `UserSlice` borrow instead of
+    /// bouncing through a kernel-allocated buffer (see `memory::uaccess::UserSlice`). Unlike
+    /// `sys_read`'s tight spin, a `WouldBlock` result (e.g. from `Stdin`, which never blocks
+    /// internally) is retried after yielding, so a not-yet-ready device doesn't stall the
+    /// whole async call poller.
     pub async fn async_read(
         &self,
         fd: usize,
@@ -10,13 +16,19 @@ impl AsyncCall {
         count: usize,
         offset: usize,
     ) -> AsyncCallResult {
-        let file = self.thread.shared_res.files.lock().get_file(fd)?;
-        let mut buf = vec![0u8; count];
-        let count = file.read(offset, &mut buf)?;
-        base.write_array(&buf[..count])?;
-        Ok(count)
+        let file = self.thread.process.shared_res.files.lock().get_file(fd)?;
+        let mut buf = base.borrow_mut(count)?;
+        loop {
+            match file.read(offset, &mut buf) {
+                Ok(n) => return Ok(n),
+                Err(AcoreError::WouldBlock) => yield_now().await,
+                Err(e) => return Err(e),
+            }
+        }
     }
 
+    /// Writes in place out of the user buffer via a zero-copy `UserSlice` borrow instead of
+    /// bouncing through a kernel-allocated buffer (see `memory::uaccess::UserSlice`).
     pub async fn async_write(
         &self,
         fd: usize,
@@ -24,8 +36,8 @@ impl AsyncCall {
         count: usize,
         offset: usize,
     ) -> AsyncCallResult {
-        let file = self.thread.shared_res.files.lock().get_file(fd)?;
-        let buf = base.read_array(count)?;
+        let file = self.thread.process.shared_res.files.lock().get_file(fd)?;
+        let buf = base.borrow(count)?;
         file.write(offset, &buf)
     }
 
@@ -35,9 +47,9 @@ impl AsyncCall {
     }
 
     pub async fn async_close(&self, fd: usize) -> AsyncCallResult {
-        let file = self.thread.shared_res.files.lock().get_file(fd)?;
+        let file = self.thread.process.shared_res.files.lock().get_file(fd)?;
         file.release()?;
-        self.thread.shared_res.files.lock().remove_file(fd)?;
+        self.thread.process.shared_res.files.lock().remove_file(fd)?;
         Ok(0)
     }
 }
@@ -1,8 +1,10 @@
 mod fs;
 mod structs;
+mod timer;
 
 use alloc::{boxed::Box, sync::Arc};
 use core::convert::TryFrom;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use core::{
     future::Future,
     pin::Pin,
@@ -12,7 +14,7 @@ use core::{
 use spin::Mutex;
 
 use crate::arch::cpu;
-use crate::config::IO_CPU_ID;
+use crate::config::{CPU_NUM, IO_CPU_MASK};
 use crate::error::{AcoreError, AcoreResult};
 use crate::memory::{
     addr::{is_aligned, virt_to_phys},
@@ -21,19 +23,40 @@ use crate::memory::{
 };
 use crate::sched::yield_now;
 use crate::task::{PerCpu, Thread};
-use structs::{AsyncCallType, CompletionRingEntry, RequestRingEntry};
+use structs::{AsyncCallType, CompletionRingEntry, RequestFlags, RequestRingEntry, LINKED_FD};
 
 pub use structs::{AsyncCallBuffer, AsyncCallInfoUser};
 
+/// Requests drained by one `polling_once` call, so a thread that keeps its ring full can't
+/// starve every other thread's I/O on this CPU; whatever's left resumes on the next poll.
+const MAX_REQUESTS_PER_POLL: u32 = 64;
+
+/// Wall-clock ticks one `polling_once` call may spend once it starts, checked between requests
+/// so a handful of slow ones can't bypass `MAX_REQUESTS_PER_POLL`'s intent.
+const POLL_TIME_SLICE_TICKS: u64 = 10;
+
+/// `FLAG_LINK` chain state that must survive across `polling_once` calls: a chain can still be
+/// in progress when one poll's fairness quota runs out, and the next poll needs to pick up
+/// where it left off instead of treating the next entry as the start of a fresh chain.
+#[derive(Default)]
+struct ChainState {
+    cancel_chain: bool,
+    linked_fd: Option<usize>,
+}
+
 pub struct AsyncCall {
     thread: Arc<Thread>,
+    chain_state: Mutex<ChainState>,
 }
 
 type AsyncCallResult = AcoreResult<usize>;
 
 impl AsyncCall {
     pub fn new(thread: Arc<Thread>) -> Self {
-        Self { thread }
+        Self {
+            thread,
+            chain_state: Mutex::new(ChainState::default()),
+        }
     }
 
     pub fn setup(
@@ -45,14 +68,20 @@ impl AsyncCall {
         if thread.owned_res.async_buf.lock().is_some() {
             return Err(AcoreError::AlreadyExists);
         }
-        let buf = AsyncCallBuffer::new(req_capacity, comp_capacity)?;
+        let max_entries = thread
+            .process
+            .shared_res
+            .limits
+            .get(crate::task::Resource::AsyncCallEntries)
+            .soft;
+        let buf = AsyncCallBuffer::new(req_capacity, comp_capacity, max_entries)?;
         let buf_size = buf.size();
         let start_paddr = virt_to_phys(buf.as_ptr::<u8>() as usize);
         let end_paddr = start_paddr + buf_size;
         debug_assert!(is_aligned(start_paddr));
 
         // push to user's MemorySet
-        let mut vm = thread.vm.lock();
+        let mut vm = thread.process.vm.lock();
         let pma = PmAreaFixed::new(start_paddr, end_paddr)?;
         let user_buf_ptr = vm.find_free_area(PAGE_SIZE, buf_size)?;
         let vma = VmArea::new(
@@ -72,7 +101,14 @@ impl AsyncCall {
         Ok(info)
     }
 
-    async fn do_async_call(&self, req: &RequestRingEntry) -> AsyncCallResult {
+    /// `linked_fd` is the fd the current chain carries, if any (see `LINKED_FD`): used in
+    /// place of `req.fd` when the submitter chained this entry to thread e.g. the fd an `Open`
+    /// earlier in the chain returned straight into a following `Read`/`Close`.
+    async fn do_async_call(
+        &self,
+        req: &RequestRingEntry,
+        linked_fd: Option<usize>,
+    ) -> AsyncCallResult {
         if self.thread.is_exited() {
             return Err(AcoreError::BadState);
         }
@@ -84,7 +120,11 @@ impl AsyncCall {
             }
         };
         debug!("AsyncCall: {:?} => {:x?}", ac_type, req);
-        let fd = req.fd as usize;
+        let fd = if req.fd == LINKED_FD {
+            linked_fd.ok_or(AcoreError::InvalidArgs)?
+        } else {
+            req.fd as usize
+        };
         let flags = req.flags as usize;
         let offset = req.offset as usize;
         let user_buf_addr = req.user_buf_addr as usize;
@@ -101,6 +141,8 @@ impl AsyncCall {
             }
             AsyncCallType::Open => self.async_open(user_buf_addr.into(), flags).await,
             AsyncCallType::Close => self.async_close(fd).await,
+            AsyncCallType::Timeout => self.async_timeout(req.offset).await,
+            AsyncCallType::Sleep => self.async_sleep(req.offset).await,
             _ => {
                 warn!("asynca call unimplemented: {:?}", ac_type);
                 Err(AcoreError::NotSupported)
@@ -114,6 +156,23 @@ impl AsyncCall {
         ret
     }
 
+    /// Post a completion for `user_data`, waiting for room in the completion ring if it's full.
+    async fn post_completion(
+        &self,
+        buf: &AsyncCallBuffer,
+        cached_comp_tail: &mut u32,
+        user_data: u64,
+        res: AsyncCallResult,
+    ) -> AcoreResult {
+        while buf.completion_count(*cached_comp_tail)? == buf.comp_capacity {
+            yield_now().await;
+        }
+        *buf.comp_entry_at(*cached_comp_tail) = CompletionRingEntry::new(user_data, res);
+        *cached_comp_tail += 1;
+        buf.write_comp_ring_tail(*cached_comp_tail);
+        Ok(())
+    }
+
     async fn polling_once(&self) -> AcoreResult {
         let buf_lock = self.thread.owned_res.async_buf.lock();
         let buf = match buf_lock.as_ref() {
@@ -125,20 +184,44 @@ impl AsyncCall {
         let mut cached_req_head = buf.read_req_ring_head();
         let mut cached_comp_tail = buf.read_comp_ring_tail();
         let req_count = buf.request_count(cached_req_head)?;
-        // TODO: limit requests count or time for one thread
-        for _ in 0..req_count {
-            if self.thread.is_exited() {
+        // Once a `FLAG_LINK` entry fails, every entry still chained to it is skipped and
+        // completed with `Cancelled` instead of run, until the chain's last (non-linked) entry.
+        // `FLAG_DRAIN` needs no extra handling: entries are already dispatched one at a time, so
+        // there is never more than one request in flight for it to wait on.
+        // TODO: dispatch non-linked entries concurrently instead of one at a time.
+        let mut chain = self.chain_state.lock();
+        // Capped by both a request quota and a time slice: a thread with a deep ring can't
+        // monopolize this I/O CPU, and whatever's left of `req_count` just resumes on the next
+        // `polling_once` (the `cached_req_head` we wrote back is where it left off).
+        let quota = req_count.min(MAX_REQUESTS_PER_POLL);
+        let deadline = crate::arch::timer::tick_count() + POLL_TIME_SLICE_TICKS;
+        for _ in 0..quota {
+            if self.thread.is_exited() || crate::arch::timer::tick_count() >= deadline {
                 break;
             }
             let req_entry = buf.req_entry_at(cached_req_head);
-            let res = self.do_async_call(&req_entry).await;
-            while buf.completion_count(cached_comp_tail)? == buf.comp_capacity {
-                yield_now().await;
+            let linked = req_entry.flags().contains(RequestFlags::LINK);
+            let res = if chain.cancel_chain {
+                Err(AcoreError::Cancelled)
+            } else {
+                self.do_async_call(req_entry, chain.linked_fd).await
+            };
+            if res.is_err() {
+                chain.cancel_chain = true;
+            }
+            chain.linked_fd = if !linked {
+                None
+            } else if req_entry.fd == LINKED_FD {
+                // This entry consumed the chain's fd; pass the same fd on to the next one.
+                chain.linked_fd
+            } else {
+                res.as_ref().ok().copied()
+            };
+            if !linked {
+                chain.cancel_chain = false;
             }
-            *buf.comp_entry_at(cached_comp_tail) =
-                CompletionRingEntry::new(req_entry.user_data, res);
-            cached_comp_tail += 1;
-            buf.write_comp_ring_tail(cached_comp_tail);
+            self.post_completion(buf, &mut cached_comp_tail, req_entry.user_data, res)
+                .await?;
             cached_req_head += 1;
         }
         buf.write_req_ring_head(cached_req_head);
@@ -155,6 +238,9 @@ impl AsyncCall {
             }
             yield_now().await;
         }
+        if let Some(cpu_id) = self.thread.owned_res.io_cpu.lock().take() {
+            POLLING_COUNT[cpu_id].fetch_sub(1, Ordering::Relaxed);
+        }
         info!("async call polling for thread {} is done.", self.thread.id);
     }
 }
@@ -181,22 +267,49 @@ impl Future for AsyncCallSwitchFuture {
     }
 }
 
+/// Per-CPU count of resident async-call polling coroutines — incremented in `spawn_polling`,
+/// decremented when `polling()` returns -- consulted by `least_loaded_io_cpu` so pinning a
+/// coroutine to one CPU up front doesn't serialize every thread's async I/O behind it.
+static POLLING_COUNT: [AtomicUsize; CPU_NUM] = {
+    const ZERO: AtomicUsize = AtomicUsize::new(0);
+    [ZERO; CPU_NUM]
+};
+
+/// The I/O-capable CPU, among those `IO_CPU_MASK` permits, with the fewest resident polling
+/// coroutines. Falls back to the current CPU if the mask permits none.
+fn least_loaded_io_cpu() -> usize {
+    (0..CPU_NUM)
+        .filter(|&id| IO_CPU_MASK.contains(id))
+        .min_by_key(|&id| POLLING_COUNT[id].load(Ordering::Relaxed))
+        .unwrap_or_else(PerCpu::id)
+}
+
 fn spawn_polling(thread: &Arc<Thread>) {
+    let cpu_id = least_loaded_io_cpu();
+    *thread.owned_res.io_cpu.lock() = Some(cpu_id);
+    POLLING_COUNT[cpu_id].fetch_add(1, Ordering::Relaxed);
+
     let ac = AsyncCall::new(thread.clone());
-    PerCpu::from_cpu_id(IO_CPU_ID).spawn(AsyncCallSwitchFuture::new(
-        thread.clone(),
-        Box::pin(async move { ac.polling().await }),
-    ));
-    cpu::send_ipi(IO_CPU_ID);
+    // Pinned to whichever I/O-capable CPU was least loaded at spawn time: the coroutine must
+    // not be work-stolen elsewhere afterwards, since that would leave POLLING_COUNT pointing at
+    // the wrong CPU.
+    PerCpu::from_cpu_id(cpu_id).spawn_with_affinity(
+        AsyncCallSwitchFuture::new(thread.clone(), Box::pin(async move { ac.polling().await })),
+        crate::sched::AffinityMask::only(cpu_id),
+    );
+    cpu::send_ipi(cpu_id);
 }
 
 pub fn init() {
     info!("async call init end.");
 }
 
+/// Run this CPU's ready async-call coroutines (and whatever else shares its executor) to
+/// completion, stealing from the busiest peer (see `task::PerCpu::run_until_idle`) before
+/// falling back to waiting for the next interrupt.
 pub fn run_forever() -> ! {
     loop {
-        PerCpu::from_cpu_id(IO_CPU_ID).run_until_idle();
+        PerCpu::from_current_cpu_id().run_until_idle();
         info!("no async coroutines to run, waiting for interrupt...");
         cpu::wait_for_interrupt();
     }
@@ -7,7 +7,6 @@ use super::AsyncCallResult;
 use crate::error::{AcoreError, AcoreResult};
 use crate::memory::cache::{alignup_cache_line, is_cache_line_aligned, AlignCacheLine};
 use crate::memory::{addr::page_count, Frame, VirtAddr};
-use crate::task::res_limit::MAX_ASYNC_CALL_ENTRY_NUM;
 
 numeric_enum! {
 #[repr(u8)]
@@ -18,10 +17,35 @@ pub(super) enum AsyncCallType {
     Write = 2,
     Open = 3,
     Close = 4,
+    /// Resolve with `AcoreError::TimedOut` once the tick deadline in `RequestRingEntry.offset`
+    /// is reached.
+    Timeout = 5,
+    /// Resolve with `Ok(0)` once the duration in nanoseconds in `RequestRingEntry.offset` has
+    /// elapsed.
+    Sleep = 6,
     Unknown = 0xff,
 }
 }
 
+bitflags! {
+    /// Bits of `RequestRingEntry.flags` controlling ordering against neighbouring entries, in
+    /// the same spirit as io_uring's `IOSQE_IO_LINK`/`IOSQE_IO_DRAIN`.
+    pub(super) struct RequestFlags: u32 {
+        /// Do not start the next entry until this one completes successfully; on failure, every
+        /// subsequent entry still linked to this chain is cancelled instead of run.
+        const LINK  = 1 << 0;
+        /// Wait for all previously submitted requests to finish before starting this one.
+        const DRAIN = 1 << 1;
+    }
+}
+
+/// Sentinel `RequestRingEntry.fd` meaning "use the chain's fd": the result of the most recent
+/// linked entry that named its own fd (an entry that itself used `LINKED_FD` passes the same fd
+/// on), so a `FLAG_LINK` chain (e.g. Open -> Read -> Close) can thread the fd `Open` returns into
+/// the entries linked after it without the submitter round-tripping through the completion ring
+/// to read it back first.
+pub(super) const LINKED_FD: i32 = -1;
+
 #[repr(C)]
 #[derive(Debug)]
 pub(super) struct RequestRingEntry {
@@ -36,6 +60,12 @@ pub(super) struct RequestRingEntry {
     pub user_data: u64,
 }
 
+impl RequestRingEntry {
+    pub(super) fn flags(&self) -> RequestFlags {
+        RequestFlags::from_bits_truncate(self.flags)
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Default)]
 pub(super) struct CompletionRingEntry {
@@ -109,11 +139,13 @@ impl CompletionRingEntry {
 }
 
 impl AsyncCallBuffer {
-    pub fn new(req_capacity: usize, comp_capacity: usize) -> AcoreResult<Self> {
-        if req_capacity == 0 || req_capacity > MAX_ASYNC_CALL_ENTRY_NUM {
+    /// `max_entries` is the task's `Resource::AsyncCallEntries` soft limit; `req_capacity`/
+    /// `comp_capacity` exceeding it is rejected instead of silently clamped.
+    pub fn new(req_capacity: usize, comp_capacity: usize, max_entries: usize) -> AcoreResult<Self> {
+        if req_capacity == 0 || req_capacity > max_entries {
             return Err(AcoreError::InvalidArgs);
         }
-        if comp_capacity == 0 || comp_capacity > MAX_ASYNC_CALL_ENTRY_NUM {
+        if comp_capacity == 0 || comp_capacity > max_entries {
             return Err(AcoreError::InvalidArgs);
         }
         let req_capacity = req_capacity.next_power_of_two() as u32;
@@ -0,0 +1,22 @@
+use super::{AsyncCall, AsyncCallResult};
+use crate::error::AcoreError;
+use crate::sched::Timer;
+
+impl AsyncCall {
+    /// Park until tick `deadline`, then complete with `TimedOut`. A pure timeout: it never
+    /// succeeds, so any entry `FLAG_LINK`ed after it is cancelled rather than run.
+    pub async fn async_timeout(&self, deadline: u64) -> AsyncCallResult {
+        let now = crate::arch::timer::tick_count();
+        Timer::after(deadline.saturating_sub(now)).await;
+        Err(AcoreError::TimedOut)
+    }
+
+    /// Sleep for (at least) `nanos` nanoseconds, then complete with `Ok(0)`.
+    pub async fn async_sleep(&self, nanos: u64) -> AsyncCallResult {
+        let ticks = crate::arch::timer::nanos_to_ticks(nanos);
+        if ticks > 0 {
+            Timer::after(ticks).await;
+        }
+        Ok(0)
+    }
+}
@@ -13,6 +13,9 @@ pub enum AcoreError {
     NotFound = -8,
     AlreadyExists = -9,
     AccessDenied = -10,
+    TimedOut = -11,
+    Cancelled = -12,
+    WouldBlock = -13,
 }
 
 pub type AcoreResult<T = ()> = Result<T, AcoreError>;
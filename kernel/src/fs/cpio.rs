@@ -0,0 +1,179 @@
+use alloc::{
+    collections::btree_map::BTreeMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::fmt::{Debug, Formatter, Result};
+
+use spin::Mutex;
+
+use super::file::File;
+use super::vfs::{Inode, InodeType};
+use super::GenericFile;
+use crate::error::{AcoreError, AcoreResult};
+
+const MAGIC: &[u8] = b"070701";
+const HEADER_SIZE: usize = 110;
+const TRAILER_NAME: &str = "TRAILER!!!";
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+fn parse_hex8(field: &[u8]) -> AcoreResult<usize> {
+    let s = core::str::from_utf8(field).map_err(|_| AcoreError::InvalidArgs)?;
+    usize::from_str_radix(s, 16).map_err(|_| AcoreError::InvalidArgs)
+}
+
+/// A regular file backed by a fixed `[offset, offset + size)` window of the ramdisk, as
+/// described by one entry of the cpio archive.
+#[derive(Debug)]
+struct CpioFile(File);
+
+impl GenericFile for CpioFile {
+    fn read(&self, offset: usize, buf: &mut [u8]) -> AcoreResult<usize> {
+        self.0.read(offset, buf)
+    }
+    fn write(&self, offset: usize, buf: &[u8]) -> AcoreResult<usize> {
+        self.0.write(offset, buf)
+    }
+    fn as_file(&self) -> AcoreResult<File> {
+        Ok(self.0.clone())
+    }
+}
+
+impl Inode for CpioFile {
+    fn inode_type(&self) -> InodeType {
+        InodeType::File
+    }
+}
+
+enum CpioNode {
+    Dir(Arc<CpioDir>),
+    File(Arc<CpioFile>),
+}
+
+impl CpioNode {
+    fn as_inode(&self) -> Arc<dyn Inode> {
+        match self {
+            CpioNode::Dir(d) => d.clone(),
+            CpioNode::File(f) => f.clone(),
+        }
+    }
+}
+
+/// A directory built out of the cpio archive's entries. The whole tree is built once while
+/// parsing the archive and is read-only afterwards, so `create` just errors like any other
+/// read-only filesystem would.
+struct CpioDir {
+    children: Mutex<BTreeMap<String, CpioNode>>,
+}
+
+impl CpioDir {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            children: Mutex::new(BTreeMap::new()),
+        })
+    }
+}
+
+impl GenericFile for CpioDir {}
+
+impl Inode for CpioDir {
+    fn inode_type(&self) -> InodeType {
+        InodeType::Dir
+    }
+
+    fn lookup(&self, name: &str) -> AcoreResult<Arc<dyn Inode>> {
+        self.children
+            .lock()
+            .get(name)
+            .map(CpioNode::as_inode)
+            .ok_or(AcoreError::NotFound)
+    }
+
+    fn list(&self) -> AcoreResult<Vec<String>> {
+        Ok(self.children.lock().keys().cloned().collect())
+    }
+}
+
+impl Debug for CpioDir {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("CpioDir")
+            .field("num_children", &self.children.lock().len())
+            .finish()
+    }
+}
+
+/// Parse a newc-format cpio archive (as produced by `bsdtar --format=newc` or `cpio -H newc`)
+/// out of the front of the ramdisk window, returning the root directory of the resulting tree.
+///
+/// Each entry starts with a fixed 110-byte header: a 6-byte `070701` magic followed by thirteen
+/// 8-digit ASCII-hex fields (ino, mode, uid, gid, nlink, mtime, filesize, dev/rdev major/minor,
+/// namesize, check). The name follows the header immediately; the file data and the next header
+/// each start on the next 4-byte boundary. Parsing stops at the conventional `TRAILER!!!`
+/// end-of-archive entry.
+pub fn parse(archive: &[u8]) -> AcoreResult<Arc<dyn Inode>> {
+    let root = CpioDir::new();
+    let mut pos = 0;
+    while pos + HEADER_SIZE <= archive.len() {
+        let header = &archive[pos..pos + HEADER_SIZE];
+        if &header[0..6] != MAGIC {
+            warn!("bad cpio magic at offset {:#x}", pos);
+            return Err(AcoreError::InvalidArgs);
+        }
+        let filesize = parse_hex8(&header[54..62])?;
+        let namesize = parse_hex8(&header[94..102])?;
+
+        let name_start = pos + HEADER_SIZE;
+        let name_end = name_start + namesize;
+        if namesize == 0 || name_end > archive.len() {
+            return Err(AcoreError::InvalidArgs);
+        }
+        // `namesize` counts the terminating NUL.
+        let name = core::str::from_utf8(&archive[name_start..name_end - 1])
+            .map_err(|_| AcoreError::InvalidArgs)?;
+
+        let data_start = align4(name_end);
+        let data_end = data_start + filesize;
+        if data_end > archive.len() {
+            return Err(AcoreError::InvalidArgs);
+        }
+
+        if name == TRAILER_NAME {
+            break;
+        }
+        insert(&root, name, data_start, filesize);
+
+        pos = align4(data_end);
+    }
+    Ok(root)
+}
+
+/// Insert a single archive entry at `path` into the tree rooted at `root`, creating any missing
+/// intermediate directories along the way.
+fn insert(root: &Arc<CpioDir>, path: &str, offset_in_disk: usize, size: usize) {
+    let mut dir = root.clone();
+    let mut comps = path.split('/').filter(|s| !s.is_empty()).peekable();
+    while let Some(comp) = comps.next() {
+        if comps.peek().is_none() {
+            let file = Arc::new(CpioFile(File::new(comp.to_string(), offset_in_disk, size)));
+            dir.children
+                .lock()
+                .insert(comp.to_string(), CpioNode::File(file));
+            break;
+        }
+        let mut children = dir.children.lock();
+        let next = match children.get(comp) {
+            Some(CpioNode::Dir(d)) => d.clone(),
+            _ => {
+                let new_dir = CpioDir::new();
+                children.insert(comp.to_string(), CpioNode::Dir(new_dir.clone()));
+                new_dir
+            }
+        };
+        drop(children);
+        dir = next;
+    }
+}
@@ -1,3 +1,4 @@
+use alloc::collections::BTreeMap;
 use alloc::string::String;
 use core::fmt::{Debug, Formatter, Result};
 
@@ -7,6 +8,7 @@ use super::GenericFile;
 use crate::error::AcoreResult;
 use crate::memory::addr::{phys_to_virt, PhysAddr};
 use crate::memory::{DEVICE_END, DEVICE_START};
+use crate::utils::IdAllocator;
 
 pub const ELF_SIZE: usize = (DEVICE_END - DEVICE_START) >> 1;
 pub const MEMORY_FILE_START: usize = DEVICE_START + ELF_SIZE;
@@ -19,6 +21,7 @@ pub struct Disk {
     _size: usize,
 }
 
+#[derive(Clone)]
 pub struct File {
     path: String,
     offset_in_disk: usize,
@@ -28,6 +31,16 @@ pub struct File {
 lazy_static! {
     pub static ref RAM_DISK: Mutex<Disk> =
         Mutex::new(Disk::new(DEVICE_START, DEVICE_END - DEVICE_START));
+    /// Which ramdisk slot (see `MEMORY_FILE_*`) each `mem:`-scheme path currently owns, so two
+    /// distinct paths never collide on the same storage and re-opening a path returns the same
+    /// slot instead of a fresh one. Slots are handed out by `MEM_FILE_SLOTS.1` on first open and
+    /// never freed, matching the rest of the ramdisk (there is no `unlink`/`close`-driven reclaim
+    /// here yet). Slot `id` lives at ramdisk offset `id * MEMORY_FILE_SIZE`, so ids start past
+    /// the initrd half and stay within `MEMORY_FILE_START..MEMORY_FILE_END`.
+    static ref MEM_FILE_SLOTS: Mutex<(BTreeMap<String, usize>, IdAllocator)> = Mutex::new((
+        BTreeMap::new(),
+        IdAllocator::new(ELF_SIZE / MEMORY_FILE_SIZE..ELF_SIZE / MEMORY_FILE_SIZE + MEMORY_FILE_MAX_COUNT)
+    ));
 }
 
 impl Disk {
@@ -40,13 +53,15 @@ impl Disk {
         }
     }
 
-    pub fn lookup(&mut self, path: &str) -> File {
-        File::new(path.into(), 0, ELF_SIZE)
+    /// The front half of the ramdisk window, reserved for the cpio initrd image (see
+    /// `fs::cpio::parse`).
+    pub(super) fn initrd(&self) -> &'static [u8] {
+        unsafe { core::slice::from_raw_parts(self.data.as_ptr(), ELF_SIZE) }
     }
 }
 
 impl File {
-    fn new(path: String, offset_in_disk: usize, size: usize) -> Self {
+    pub(super) fn new(path: String, offset_in_disk: usize, size: usize) -> Self {
         Self {
             path,
             offset_in_disk,
@@ -54,8 +69,19 @@ impl File {
         }
     }
 
+    /// Get (or, on first open, allocate) `path`'s dedicated ramdisk slot. Distinct paths used
+    /// to collide whenever `path.len()` matched modulo `MEMORY_FILE_MAX_COUNT`; each path now
+    /// gets its own slot for as long as the kernel runs.
     pub fn new_memory_file(path: String) -> AcoreResult<Self> {
-        let id = path.len() as usize % MEMORY_FILE_MAX_COUNT;
+        let mut slots = MEM_FILE_SLOTS.lock();
+        let id = match slots.0.get(&path) {
+            Some(&id) => id,
+            None => {
+                let id = slots.1.alloc()?;
+                slots.0.insert(path.clone(), id);
+                id
+            }
+        };
         Ok(File::new(path, id * MEMORY_FILE_SIZE, MEMORY_FILE_SIZE))
     }
 
@@ -79,6 +105,10 @@ impl GenericFile for File {
         RAM_DISK.lock().data[offset..offset + len].copy_from_slice(buf);
         Ok(len)
     }
+
+    fn as_file(&self) -> AcoreResult<Self> {
+        Ok(self.clone())
+    }
 }
 
 impl Debug for File {
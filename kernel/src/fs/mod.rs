@@ -1,5 +1,8 @@
+mod cpio;
 mod file;
+mod scheme;
 mod stdio;
+mod vfs;
 
 use alloc::{sync::Arc, vec::Vec};
 use core::fmt::{Debug, Formatter, Result};
@@ -9,6 +12,8 @@ use crate::utils::IdAllocator;
 use stdio::{Stdin, Stdout};
 
 pub use file::{File, RAM_DISK};
+pub use scheme::{register, SchemeProvider};
+pub use vfs::{open_path, Inode, InodeType};
 
 pub trait GenericFile: Send + Sync + Debug {
     fn open(&self) -> AcoreResult {
@@ -23,6 +28,29 @@ pub trait GenericFile: Send + Sync + Debug {
     fn write(&self, _offset: usize, _buf: &[u8]) -> AcoreResult<usize> {
         Err(AcoreError::NotSupported)
     }
+    /// Get the underlying ramdisk-backed `File`, e.g. for loading an ELF image. Only
+    /// implemented by inodes whose data is a fixed window of the ramdisk rather than paged in
+    /// on demand from somewhere else.
+    fn as_file(&self) -> AcoreResult<File> {
+        Err(AcoreError::NotSupported)
+    }
+}
+
+/// Register the built-in schemes, then parse the cpio initrd baked into the front half of the
+/// ramdisk window and mount it as the root of the VFS, so `open_path` can resolve absolute
+/// paths like `/init`. Must run once after `memory::init()` has mapped the ramdisk in, and
+/// before the first `open_path` or `open` call.
+pub fn init() -> AcoreResult {
+    scheme::init();
+    let root = cpio::parse(RAM_DISK.lock().initrd())?;
+    vfs::mount_root(root);
+    Ok(())
+}
+
+/// Resolve `path`'s leading `scheme:` prefix (`stdin:`, `stdout:`, `null:`, or `mem:` by
+/// default) to a file object via the scheme registry. Used by `sys_openat`.
+pub fn open(path: &str) -> AcoreResult<Arc<dyn GenericFile>> {
+    scheme::open(path)
 }
 
 pub struct FileStruct {
@@ -67,6 +95,19 @@ impl FileStruct {
         self.fd_allocator.dealloc(fd);
         Ok(())
     }
+
+    /// Raise the open-file ceiling to `new_max`, e.g. when `sys_setrlimit` raises `NoFile`.
+    /// Only grows: shrinking would mean either force-closing already-open fds at or above the
+    /// new ceiling or letting them linger past it, and nothing here needs that yet.
+    pub fn resize(&mut self, new_max: usize) -> AcoreResult {
+        let old_max = self.files.len();
+        if new_max < old_max {
+            return Err(AcoreError::InvalidArgs);
+        }
+        self.files.resize(new_max, None);
+        self.fd_allocator.grow(old_max, new_max);
+        Ok(())
+    }
 }
 
 impl Drop for FileStruct {
@@ -0,0 +1,74 @@
+//! Scheme registry: maps a path's leading `scheme:` prefix (`stdin:`, `stdout:`, `null:`,
+//! `mem:`) to the provider that resolves the rest of the path to a `GenericFile`, so
+//! `sys_openat` can dispatch by scheme instead of always building an in-memory file.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+
+use spin::Mutex;
+
+use super::file::File;
+use super::stdio::{Null, Stdin, Stdout};
+use super::GenericFile;
+use crate::error::{AcoreError, AcoreResult};
+
+/// Resolves the part of a path after the `scheme:` prefix to a file object. Implemented once
+/// per scheme and wired in with `register`.
+pub trait SchemeProvider: Send + Sync {
+    fn open(&self, path: &str) -> AcoreResult<Arc<dyn GenericFile>>;
+}
+
+/// A scheme backed by a single shared `GenericFile`, ignoring the rest of the path -- the
+/// common case for device-like schemes such as `stdin:`/`stdout:`/`null:`.
+struct Singleton(Arc<dyn GenericFile>);
+
+impl SchemeProvider for Singleton {
+    fn open(&self, _path: &str) -> AcoreResult<Arc<dyn GenericFile>> {
+        Ok(self.0.clone())
+    }
+}
+
+/// `mem:`, the scheme `sys_openat` used unconditionally before this registry existed: each
+/// distinct path gets its own dedicated ramdisk-backed slot, allocated on first open (see
+/// `File::new_memory_file`).
+struct MemScheme;
+
+impl SchemeProvider for MemScheme {
+    fn open(&self, path: &str) -> AcoreResult<Arc<dyn GenericFile>> {
+        Ok(Arc::new(File::new_memory_file(String::from(path))?))
+    }
+}
+
+lazy_static! {
+    static ref SCHEMES: Mutex<BTreeMap<&'static str, Arc<dyn SchemeProvider>>> =
+        Mutex::new(BTreeMap::new());
+}
+
+/// Register `provider` as the handler for `scheme` (without the trailing `:`).
+pub fn register(scheme: &'static str, provider: Arc<dyn SchemeProvider>) {
+    SCHEMES.lock().insert(scheme, provider);
+}
+
+/// Register the built-in schemes. Called once from `fs::init`.
+pub(super) fn init() {
+    register("mem", Arc::new(MemScheme));
+    register("stdin", Arc::new(Singleton(Arc::new(Stdin))));
+    register("stdout", Arc::new(Singleton(Arc::new(Stdout))));
+    register("null", Arc::new(Singleton(Arc::new(Null))));
+}
+
+/// Split `path` on its first `:` into a scheme and the rest, defaulting to `mem` (and the whole
+/// path) if there is none, then dispatch to the matching registered provider.
+pub(super) fn open(path: &str) -> AcoreResult<Arc<dyn GenericFile>> {
+    let (scheme, rest) = match path.find(':') {
+        Some(idx) => (&path[..idx], &path[idx + 1..]),
+        None => ("mem", path),
+    };
+    let provider = SCHEMES
+        .lock()
+        .get(scheme)
+        .cloned()
+        .ok_or(AcoreError::NotFound)?;
+    provider.open(rest)
+}
@@ -7,9 +7,33 @@ pub struct Stdin;
 #[derive(Debug)]
 pub struct Stdout;
 
+/// `null:`, a `GenericFile` that discards all writes and reads as EOF.
+#[derive(Debug)]
+pub struct Null;
+
 impl GenericFile for Stdin {
-    fn read(&self, _offset: usize, _buf: &mut [u8]) -> AcoreResult<usize> {
-        Err(AcoreError::NotSupported)
+    /// Poll `arch::io::getchar` once per requested byte, stopping -- without spinning -- the
+    /// moment nothing more is buffered: there's no input interrupt wired up yet, and spinning
+    /// here would hang whatever is calling `read`, including the async call poller, instead of
+    /// just this one request. Returns the bytes actually read, or `WouldBlock` if that's zero;
+    /// `sys_read` retries in a tight loop so a synchronous caller still sees an ordinary
+    /// blocking read, while `AsyncCall::async_read` yields between retries instead.
+    fn read(&self, _offset: usize, buf: &mut [u8]) -> AcoreResult<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            match crate::arch::io::getchar() {
+                Some(byte) => {
+                    buf[n] = byte;
+                    n += 1;
+                }
+                None => break,
+            }
+        }
+        if n == 0 {
+            Err(AcoreError::WouldBlock)
+        } else {
+            Ok(n)
+        }
     }
 
     fn write(&self, _offset: usize, _buf: &[u8]) -> AcoreResult<usize> {
@@ -28,3 +52,13 @@ impl GenericFile for Stdout {
         Ok(buf.len())
     }
 }
+
+impl GenericFile for Null {
+    fn read(&self, _offset: usize, _buf: &mut [u8]) -> AcoreResult<usize> {
+        Ok(0)
+    }
+
+    fn write(&self, _offset: usize, buf: &[u8]) -> AcoreResult<usize> {
+        Ok(buf.len())
+    }
+}
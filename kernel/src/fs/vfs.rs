@@ -0,0 +1,55 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+
+use spin::Mutex;
+
+use super::GenericFile;
+use crate::error::{AcoreError, AcoreResult};
+
+/// Whether an `Inode` is a regular file or a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InodeType {
+    File,
+    Dir,
+}
+
+/// A node in the VFS tree. A regular file only ever uses `GenericFile`'s `read`/`write`; a
+/// directory additionally implements `lookup`/`list`/`create`. Both kinds get `NotSupported`
+/// defaults for the methods that don't apply to them, the same way `GenericFile` itself does.
+pub trait Inode: GenericFile {
+    fn inode_type(&self) -> InodeType;
+
+    /// Look up a direct child of a directory inode by name.
+    fn lookup(&self, _name: &str) -> AcoreResult<Arc<dyn Inode>> {
+        Err(AcoreError::NotSupported)
+    }
+
+    /// List the names of a directory inode's direct children.
+    fn list(&self) -> AcoreResult<Vec<String>> {
+        Err(AcoreError::NotSupported)
+    }
+
+    /// Create a new child of a directory inode.
+    fn create(&self, _name: &str, _type_: InodeType) -> AcoreResult<Arc<dyn Inode>> {
+        Err(AcoreError::NotSupported)
+    }
+}
+
+lazy_static! {
+    static ref ROOT_INODE: Mutex<Option<Arc<dyn Inode>>> = Mutex::new(None);
+}
+
+/// Mount `root` as the root of the VFS tree. Called once at boot, after the backing driver
+/// (e.g. `cpio::parse`) has built the tree.
+pub(super) fn mount_root(root: Arc<dyn Inode>) {
+    *ROOT_INODE.lock() = Some(root);
+}
+
+/// Resolve an absolute path (e.g. `/bin/sh`) to its `Inode`, walking down from the mounted root
+/// one path component at a time.
+pub fn open_path(path: &str) -> AcoreResult<Arc<dyn Inode>> {
+    let mut inode = ROOT_INODE.lock().clone().ok_or(AcoreError::NotFound)?;
+    for comp in path.split('/').filter(|s| !s.is_empty()) {
+        inode = inode.lookup(comp)?;
+    }
+    Ok(inode)
+}
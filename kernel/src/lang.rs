@@ -4,10 +4,23 @@ use core::panic::PanicInfo;
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     error!("\n{}", info);
+    crate::arch::dump_backtrace();
     loop {}
 }
 
+/// Kernel heap exhaustion. Frame-backed allocations (`Frame::new`, `PmArea::get_frame`, ...)
+/// already surface exhaustion as a recoverable `AcoreError::NoMemory` instead of landing here
+/// (see `memory::frame::alloc_frame`'s `Option` return); this is only reached for the kernel's
+/// own global heap (`Vec`/`Box`/`Arc`/...), whose allocator trait gives no way to propagate a
+/// failure back to the caller as a `Result`. There is currently no fallback arena to retry from,
+/// so this still has to stop the kernel -- but it logs the failed request's size and alignment
+/// first, so an OOM panic is diagnosable instead of just a bare message.
 #[lang = "oom"]
-fn oom(_: Layout) -> ! {
+fn oom(layout: Layout) -> ! {
+    error!(
+        "kernel heap exhausted: requested {} bytes (align {})",
+        layout.size(),
+        layout.align()
+    );
     panic!("out of memory");
 }
@@ -65,6 +65,7 @@ pub extern "C" fn start_kernel(arg0: usize, arg1: usize) -> ! {
 
 pub fn normal_main() -> ! {
     info!("Hello, normal CPU!");
+    fs::init().unwrap();
     task::init();
     task::run_forever();
 }
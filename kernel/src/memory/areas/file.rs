@@ -0,0 +1,232 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt::{Debug, Formatter, Result};
+
+use spin::Mutex;
+
+use super::{PmArea, VmArea};
+use crate::error::{AcoreError, AcoreResult};
+use crate::fs::GenericFile;
+use crate::memory::{
+    addr::{align_down, page_count},
+    Frame, MMUFlags, PhysAddr, VirtAddr, PAGE_SIZE,
+};
+
+/// A discontiguous, file-backed PMA that loads each page from `file` lazily on its first
+/// page fault, instead of eagerly copying the whole mapped region up front (e.g. for ELF
+/// segments and a future `mmap`).
+///
+/// Bytes at `[file_offset, file_offset + file_len)` come from `file`; anything beyond that
+/// up to `size()` (e.g. the `.bss` tail of an ELF segment) is zero-filled.
+///
+/// Frames are `Arc`-wrapped so that `fork()` can hand out a clone that shares the same
+/// backing frames with the parent, copy-on-write style, exactly like `PmAreaLazy` — needed
+/// so a forked process whose ELF `.data` segment is file-backed doesn't corrupt its parent's
+/// pages on the first write.
+///
+/// `shared` distinguishes a private mapping (ELF segments: written-to pages are never sent
+/// back to the file, matching `MAP_PRIVATE`) from a shared one (`sys_mmap`'s `MAP_SHARED`,
+/// via `VmArea::from_file_pma`), whose pages within `file_len` are written back to `file` as
+/// they're evicted in `release_frame`, whether or not they were modified. Already in use by both the ELF loader (`utils::loader`) and
+/// `sys_mmap` for file-backed mappings, so program text/data segments and shared file mmaps
+/// share this one lazy, writeback-aware implementation instead of each growing their own.
+pub struct PmAreaFile {
+    file: Arc<dyn GenericFile>,
+    file_offset: usize,
+    file_len: usize,
+    shared: bool,
+    frames: Vec<Option<Arc<Frame>>>,
+}
+
+impl PmArea for PmAreaFile {
+    fn size(&self) -> usize {
+        self.frames.len() * PAGE_SIZE
+    }
+    fn get_frame(&mut self, idx: usize, need_alloc: bool) -> AcoreResult<Option<PhysAddr>> {
+        if need_alloc && self.frames[idx].is_none() {
+            let mut frame = Frame::new()?;
+            frame.zero();
+            let page_off = idx * PAGE_SIZE;
+            if page_off < self.file_len {
+                let len = PAGE_SIZE.min(self.file_len - page_off);
+                self.file
+                    .read(self.file_offset + page_off, &mut frame.as_slice_mut()[..len])?;
+            }
+            self.frames[idx] = Some(Arc::new(frame));
+        }
+        Ok(self.frames[idx].as_ref().map(|f| f.start_paddr()))
+    }
+    fn release_frame(&mut self, idx: usize) -> AcoreResult {
+        let frame = self.frames[idx].take().ok_or(AcoreError::NotFound)?;
+        let page_off = idx * PAGE_SIZE;
+        if self.shared && page_off < self.file_len {
+            let len = PAGE_SIZE.min(self.file_len - page_off);
+            self.file
+                .write(self.file_offset + page_off, &frame.as_slice()[..len])?;
+        }
+        Ok(())
+    }
+    fn fork(&mut self) -> AcoreResult<Option<Arc<Mutex<dyn PmArea>>>> {
+        Ok(Some(Arc::new(Mutex::new(Self {
+            file: self.file.clone(),
+            file_offset: self.file_offset,
+            file_len: self.file_len,
+            shared: self.shared,
+            frames: self.frames.clone(),
+        }))))
+    }
+    fn copy_on_write(&mut self, idx: usize) -> AcoreResult<PhysAddr> {
+        let frame = self.frames[idx].as_mut().ok_or(AcoreError::NotFound)?;
+        if Arc::strong_count(frame) > 1 {
+            let mut new_frame = Frame::new()?;
+            new_frame.as_slice_mut().copy_from_slice(frame.as_slice());
+            *frame = Arc::new(new_frame);
+        }
+        Ok(frame.start_paddr())
+    }
+    fn split_off(&mut self, at: usize) -> AcoreResult<Arc<Mutex<dyn PmArea>>> {
+        let split_offset = at * PAGE_SIZE;
+        let tail_frames = self.frames.split_off(at);
+        let tail_file_offset = self.file_offset + split_offset;
+        let tail_file_len = self.file_len.saturating_sub(split_offset);
+        self.file_len = self.file_len.min(split_offset);
+        Ok(Arc::new(Mutex::new(Self {
+            file: self.file.clone(),
+            file_offset: tail_file_offset,
+            file_len: tail_file_len,
+            shared: self.shared,
+            frames: tail_frames,
+        })))
+    }
+    fn read(&mut self, offset: usize, dst: &mut [u8]) -> AcoreResult<usize> {
+        self.for_each_frame(offset, dst.len(), |processed: usize, frame: &mut [u8]| {
+            dst[processed..processed + frame.len()].copy_from_slice(frame);
+        })
+    }
+    fn write(&mut self, offset: usize, src: &[u8]) -> AcoreResult<usize> {
+        self.for_each_frame(offset, src.len(), |processed: usize, frame: &mut [u8]| {
+            frame.copy_from_slice(&src[processed..processed + frame.len()]);
+        })
+    }
+}
+
+impl PmAreaFile {
+    /// Create a private (`MAP_PRIVATE`-like) file-backed PMA: writes stay local to this PMA
+    /// and are never sent back to `file`, as used for ELF segments.
+    pub fn new(
+        file: Arc<dyn GenericFile>,
+        file_offset: usize,
+        file_len: usize,
+        total_size: usize,
+    ) -> AcoreResult<Self> {
+        Self::with_sharing(file, file_offset, file_len, total_size, false)
+    }
+
+    /// Create a shared (`MAP_SHARED`-like) file-backed PMA: a page evicted via
+    /// `release_frame` is first written back to `file` if it falls within `file_len`.
+    pub fn new_shared(
+        file: Arc<dyn GenericFile>,
+        file_offset: usize,
+        file_len: usize,
+        total_size: usize,
+    ) -> AcoreResult<Self> {
+        Self::with_sharing(file, file_offset, file_len, total_size, true)
+    }
+
+    fn with_sharing(
+        file: Arc<dyn GenericFile>,
+        file_offset: usize,
+        file_len: usize,
+        total_size: usize,
+        shared: bool,
+    ) -> AcoreResult<Self> {
+        if total_size == 0 || file_len > total_size {
+            warn!(
+                "invalid PMA size in PmAreaFile::new(): file_len={:#x?}, total_size={:#x?}",
+                file_len, total_size
+            );
+            return Err(AcoreError::InvalidArgs);
+        }
+        Ok(Self {
+            file,
+            file_offset,
+            file_len,
+            shared,
+            frames: vec![None; page_count(total_size)],
+        })
+    }
+
+    fn for_each_frame(
+        &mut self,
+        offset: usize,
+        len: usize,
+        mut op: impl FnMut(usize, &mut [u8]),
+    ) -> AcoreResult<usize> {
+        if offset >= self.size() || offset + len > self.size() {
+            warn!(
+                "out of range in PmAreaFile::for_each_frame(): offset={:#x?}, len={:#x?}, {:#x?}",
+                offset, len, self
+            );
+            return Err(AcoreError::OutOfRange);
+        }
+        let mut start = offset;
+        let mut len = len;
+        let mut processed = 0;
+        while len > 0 {
+            let start_align = align_down(start);
+            let pgoff = start - start_align;
+            let n = (PAGE_SIZE - pgoff).min(len);
+
+            let idx = start_align / PAGE_SIZE;
+            self.get_frame(idx, true)?;
+            let frame = Arc::get_mut(self.frames[idx].as_mut().unwrap()).ok_or_else(|| {
+                warn!(
+                    "cannot write to a frame shared by a forked PmAreaFile: idx={:#x?}",
+                    idx
+                );
+                AcoreError::AccessDenied
+            })?;
+            op(processed, &mut frame.as_slice_mut()[pgoff..pgoff + n]);
+            start += n;
+            processed += n;
+            len -= n;
+        }
+        Ok(processed)
+    }
+}
+
+impl Debug for PmAreaFile {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("PmAreaFile")
+            .field("size", &self.size())
+            .field("file_offset", &self.file_offset)
+            .field("file_len", &self.file_len)
+            .field("shared", &self.shared)
+            .finish()
+    }
+}
+
+impl VmArea {
+    pub fn from_file_pma(
+        start_vaddr: VirtAddr,
+        file: Arc<dyn GenericFile>,
+        file_offset: usize,
+        file_len: usize,
+        total_size: usize,
+        flags: MMUFlags,
+        shared: bool,
+        name: &'static str,
+    ) -> AcoreResult<Self> {
+        let pma = if shared {
+            PmAreaFile::new_shared(file, file_offset, file_len, total_size)?
+        } else {
+            PmAreaFile::new(file, file_offset, file_len, total_size)?
+        };
+        Self::new(
+            start_vaddr,
+            start_vaddr + total_size,
+            flags,
+            Arc::new(Mutex::new(pma)),
+            name,
+        )
+    }
+}
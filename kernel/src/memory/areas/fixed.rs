@@ -29,6 +29,15 @@ impl PmArea for PmAreaFixed {
     fn release_frame(&mut self, _idx: usize) -> AcoreResult {
         Ok(())
     }
+    fn split_off(&mut self, at: usize) -> AcoreResult<Arc<Mutex<dyn PmArea>>> {
+        let split_paddr = self.start + at * PAGE_SIZE;
+        let tail = Self {
+            start: split_paddr,
+            end: self.end,
+        };
+        self.end = split_paddr;
+        Ok(Arc::new(Mutex::new(tail)))
+    }
     fn read(&mut self, offset: usize, dst: &mut [u8]) -> AcoreResult<usize> {
         if offset >= self.size() {
             warn!(
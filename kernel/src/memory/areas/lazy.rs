@@ -11,8 +11,12 @@ use crate::memory::{
 };
 
 /// A discontiguous PMA which perform lazy allocation (e.g. in page fault handler).
+///
+/// Frames are `Arc`-wrapped so that `fork()` can hand out a clone that shares the same
+/// backing frames with the parent, copy-on-write style: a shared frame is only duplicated
+/// when one of the sharing `MemorySet`s writes to it (see `copy_on_write`).
 pub struct PmAreaLazy {
-    frames: Vec<Option<Frame>>,
+    frames: Vec<Option<Arc<Frame>>>,
 }
 
 impl PmArea for PmAreaLazy {
@@ -23,7 +27,7 @@ impl PmArea for PmAreaLazy {
         if need_alloc && self.frames[idx].is_none() {
             let mut frame = Frame::new()?;
             frame.zero();
-            self.frames[idx] = Some(frame);
+            self.frames[idx] = Some(Arc::new(frame));
         }
         Ok(self.frames[idx].as_ref().map(|f| f.start_paddr()))
     }
@@ -31,6 +35,12 @@ impl PmArea for PmAreaLazy {
         self.frames[idx].take().ok_or(AcoreError::NotFound)?;
         Ok(())
     }
+    fn split_off(&mut self, at: usize) -> AcoreResult<Arc<Mutex<dyn PmArea>>> {
+        let tail_frames = self.frames.split_off(at);
+        Ok(Arc::new(Mutex::new(Self {
+            frames: tail_frames,
+        })))
+    }
     fn read(&mut self, offset: usize, dst: &mut [u8]) -> AcoreResult<usize> {
         self.for_each_frame(offset, dst.len(), |processed: usize, frame: &mut [u8]| {
             dst[processed..processed + frame.len()].copy_from_slice(frame);
@@ -41,6 +51,23 @@ impl PmArea for PmAreaLazy {
             frame.copy_from_slice(&src[processed..processed + frame.len()]);
         })
     }
+    fn fork(&mut self) -> AcoreResult<Option<Arc<Mutex<dyn PmArea>>>> {
+        // Cloning the `Arc`s bumps the refcount of every already-allocated frame; both the
+        // parent's and the child's copy of this PMA now share them read-only until one side
+        // writes and breaks the sharing in `copy_on_write`.
+        Ok(Some(Arc::new(Mutex::new(Self {
+            frames: self.frames.clone(),
+        }))))
+    }
+    fn copy_on_write(&mut self, idx: usize) -> AcoreResult<PhysAddr> {
+        let frame = self.frames[idx].as_mut().ok_or(AcoreError::NotFound)?;
+        if Arc::strong_count(frame) > 1 {
+            let mut new_frame = Frame::new()?;
+            new_frame.as_slice_mut().copy_from_slice(frame.as_slice());
+            *frame = Arc::new(new_frame);
+        }
+        Ok(frame.start_paddr())
+    }
 }
 
 impl PmAreaLazy {
@@ -91,9 +118,15 @@ impl PmAreaLazy {
             if self.frames[idx].is_none() {
                 let mut frame = Frame::new()?;
                 frame.zero();
-                self.frames[idx] = Some(frame);
+                self.frames[idx] = Some(Arc::new(frame));
             }
-            let frame = self.frames[idx].as_mut().unwrap();
+            let frame = Arc::get_mut(self.frames[idx].as_mut().unwrap()).ok_or_else(|| {
+                warn!(
+                    "cannot write to a frame shared by a forked PmAreaLazy: idx={:#x?}",
+                    idx
+                );
+                AcoreError::AccessDenied
+            })?;
             op(processed, &mut frame.as_slice_mut()[pgoff..pgoff + n]);
             start += n;
             processed += n;
@@ -112,7 +145,7 @@ impl Debug for PmAreaLazy {
 }
 
 impl VmArea {
-    pub fn from_delay_pma(
+    pub fn from_lazy_pma(
         start_vaddr: VirtAddr,
         size: usize,
         flags: MMUFlags,
@@ -122,7 +155,7 @@ impl VmArea {
             start_vaddr,
             start_vaddr + size,
             flags,
-            Arc::new(Mutex::new(PmAreaLazy::new(size)?)),
+            Arc::new(Mutex::new(PmAreaLazy::new(addr::page_count(size))?)),
             name,
         )
     }
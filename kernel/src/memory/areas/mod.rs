@@ -1,10 +1,13 @@
+mod file;
 mod fixed;
 mod lazy;
 
+pub use file::PmAreaFile;
 pub use fixed::PmAreaFixed;
 pub use lazy::PmAreaLazy;
 
 use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use spin::Mutex;
 
@@ -27,6 +30,40 @@ pub trait PmArea: core::fmt::Debug + Send + Sync {
     fn read(&mut self, offset: usize, dst: &mut [u8]) -> AcoreResult<usize>;
     /// Write data to this PMA at `offset`.
     fn write(&mut self, offset: usize, src: &[u8]) -> AcoreResult<usize>;
+
+    /// Create a copy-on-write clone of this PMA for `MemorySet::fork()`.
+    ///
+    /// Returns `Ok(None)` if this kind of PMA has no notion of private/shared frames and
+    /// should simply be shared as-is (same `Arc<Mutex<dyn PmArea>>`) between parent and
+    /// child. PMAs that do support COW (e.g. `PmAreaLazy`) return a new PMA that shares
+    /// already-allocated frames with `self` until `copy_on_write` splits them apart.
+    fn fork(&mut self) -> AcoreResult<Option<Arc<Mutex<dyn PmArea>>>> {
+        Ok(None)
+    }
+    /// Break copy-on-write sharing of the frame at `idx`, if necessary, and return its
+    /// (possibly new) physical address. Called from `VmArea::handle_page_fault` when a write
+    /// hits a present but write-protected page.
+    fn copy_on_write(&mut self, _idx: usize) -> AcoreResult<PhysAddr> {
+        Err(AcoreError::NotSupported)
+    }
+
+    /// Split this PMA at page index `at`: `self` shrinks to keep only pages `[0, at)`, and a
+    /// fresh PMA owning pages `[at, self.size() / PAGE_SIZE)` is returned. Used by
+    /// `VmArea::split_at` to carve a `MemorySet::pop()` hole out of the middle of an area
+    /// without forcing a full unmap/remap of the parts that should survive.
+    fn split_off(&mut self, _at: usize) -> AcoreResult<Arc<Mutex<dyn PmArea>>> {
+        Err(AcoreError::NotSupported)
+    }
+}
+
+/// Remove whatever page-table entries `map_area` already installed in `[start, up_to)` before
+/// hitting an error partway through. No `PmArea` frame was allocated for any of these pages (
+/// `map_area` always calls `get_frame` with `need_alloc = false`), so there's nothing to release
+/// back to the frame allocator — just the mappings themselves to undo.
+fn unmap_range_unlocked(pt: &mut impl PageTable, start: VirtAddr, up_to: VirtAddr) {
+    for vaddr in (start..up_to).step_by(PAGE_SIZE) {
+        let _ = pt.unmap(vaddr);
+    }
 }
 
 /// A contiguous virtual memory area with same MMU flags.
@@ -38,6 +75,13 @@ pub struct VmArea {
     pub(super) flags: MMUFlags,
     pub(super) pma: Arc<Mutex<dyn PmArea>>,
     name: &'static str,
+    /// Whether the backing frames of this area may be shared with another `MemorySet`
+    /// (set by `fork()`). While `true`, writable pages are mapped without `WRITE` so that
+    /// the first write traps into `handle_page_fault` and breaks the sharing.
+    cow: bool,
+    /// Number of outstanding `UserSlice` zero-copy borrows into this area (see
+    /// `memory::uaccess`). While nonzero, `MemorySet::pop` refuses to unmap it.
+    pin_count: AtomicUsize,
 }
 
 impl VmArea {
@@ -69,9 +113,37 @@ impl VmArea {
             flags,
             pma,
             name,
+            cow: false,
+            pin_count: AtomicUsize::new(0),
         })
     }
 
+    /// Pin this area for the duration of a zero-copy `UserSlice` borrow, preventing `pop()`
+    /// from unmapping it in the meantime. Must be matched by a later `unpin()`.
+    pub(super) fn pin(&self) {
+        self.pin_count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Undo one `pin()`.
+    pub(super) fn unpin(&self) {
+        self.pin_count.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Whether any `UserSlice` currently borrows into this area.
+    pub(super) fn is_pinned(&self) -> bool {
+        self.pin_count.load(Ordering::SeqCst) != 0
+    }
+
+    /// Effective MMU flags to use for page table entries: like `flags`, but with `WRITE`
+    /// stripped while this area is copy-on-write shared, so writes trap for COW handling.
+    fn effective_flags(&self) -> MMUFlags {
+        if self.cow && self.flags.contains(MMUFlags::WRITE) {
+            self.flags - MMUFlags::WRITE
+        } else {
+            self.flags
+        }
+    }
+
     /// Test whether a virtual address is contained in the memory area.
     pub fn contains(&self, vaddr: VirtAddr) -> bool {
         self.start <= vaddr && vaddr < self.end
@@ -91,28 +163,53 @@ impl VmArea {
         trace!("create mapping: {:#x?}", self);
         let mut pma = self.pma.lock();
         for vaddr in (self.start..self.end).step_by(PAGE_SIZE) {
-            let page = pma.get_frame((vaddr - self.start) / PAGE_SIZE, false)?;
+            let page = match pma.get_frame((vaddr - self.start) / PAGE_SIZE, false) {
+                Ok(page) => page,
+                Err(e) => {
+                    error!("failed to get frame for mapping: {:#x?}, {:?}", vaddr, e);
+                    unmap_range_unlocked(pt, self.start, vaddr);
+                    return Err(e);
+                }
+            };
             let res = if let Some(paddr) = page {
-                pt.map(vaddr, paddr, self.flags)
+                pt.map(vaddr, paddr, self.effective_flags())
             } else {
                 pt.map(vaddr, 0, MMUFlags::empty())
             };
-            res.map_err(|e| {
+            if let Err(e) = res {
                 error!(
                     "failed to create mapping: {:#x?} -> {:#x?}, {:?}",
                     vaddr, page, e
                 );
-                e
-            })?;
+                // Tear down whatever this loop already installed in [self.start, vaddr) so a
+                // failed map_area (e.g. running out of frames for intermediate page-table
+                // levels) never leaves stale page-table entries behind for `MemorySet::push`'s
+                // caller to propagate NoMemory past a half-mapped area.
+                unmap_range_unlocked(pt, self.start, vaddr);
+                return Err(e);
+            }
         }
         Ok(())
     }
 
     /// Destory mapping of this VMA.
     pub fn unmap_area(&self, pt: &mut impl PageTable) -> AcoreResult {
-        trace!("destory mapping: {:#x?}", self);
+        self.unmap_range(pt, self.start, self.end)
+    }
+
+    /// Release frames and unmap page table entries for `[start, end)`, a sub-range of this
+    /// area's own `[self.start, self.end)`. Used both by `unmap_area` (the whole area) and by
+    /// `MemorySet::pop`'s partial unmap, which only tears down the covered pages before
+    /// `split_at` carves the surviving head/tail back into the `areas` map.
+    ///
+    /// Issues a single `flush_tlb_range` covering the whole region once the unmap is done,
+    /// rather than one remote TLB shootdown per page — the bulk of the cost of tearing down a
+    /// large region is in the SBI round trips, not the local `sfence.vma`s.
+    pub(super) fn unmap_range(&self, pt: &mut impl PageTable, start: VirtAddr, end: VirtAddr) -> AcoreResult {
+        trace!("destory mapping: [{:#x?}, {:#x?}) of {:#x?}", start, end, self);
         let mut pma = self.pma.lock();
-        for vaddr in (self.start..self.end).step_by(PAGE_SIZE) {
+        let mut unmapped_any = false;
+        for vaddr in (start..end).step_by(PAGE_SIZE) {
             let res = pma.release_frame((vaddr - self.start) / PAGE_SIZE);
             if res != Err(AcoreError::NotFound) {
                 if res.is_err() {
@@ -122,11 +219,42 @@ impl VmArea {
                     error!("failed to unmap VA: {:#x?}, {:?}", vaddr, e);
                     e
                 })?;
+                unmapped_any = true;
             }
         }
+        if unmapped_any {
+            pt.flush_tlb_range(start, end);
+        }
         Ok(())
     }
 
+    /// Split off the tail `[at, self.end)` into a new `VmArea`, shrinking `self` to
+    /// `[self.start, at)`. `at` must be page-aligned and strictly inside `(self.start,
+    /// self.end)`. Fails with `NotSupported` if the backing `PmArea` doesn't support
+    /// `split_off`, or if the `PmArea` object itself is referenced from elsewhere (e.g. shared
+    /// as-is with a child by `fork()` because its `PmArea::fork` returned `None`) — truncating
+    /// it would cut the pages out from under the other holder too.
+    pub(super) fn split_at(&mut self, at: VirtAddr) -> AcoreResult<VmArea> {
+        debug_assert!(self.start < at && at < self.end);
+        if Arc::strong_count(&self.pma) > 1 {
+            warn!("cannot split a VMA whose PmArea is shared: {:#x?}", self);
+            return Err(AcoreError::NotSupported);
+        }
+        let at_idx = (at - self.start) / PAGE_SIZE;
+        let tail_pma = self.pma.lock().split_off(at_idx)?;
+        let tail = VmArea {
+            start: at,
+            end: self.end,
+            flags: self.flags,
+            pma: tail_pma,
+            name: self.name,
+            cow: self.cow,
+            pin_count: AtomicUsize::new(0),
+        };
+        self.end = at;
+        Ok(tail)
+    }
+
     /// Handle page fault.
     pub fn handle_page_fault(
         &self,
@@ -147,18 +275,67 @@ impl VmArea {
         }
         let offset = align_down(offset);
         let vaddr = self.start + offset;
-        let paddr = pma
-            .get_frame(offset / PAGE_SIZE, true)?
-            .ok_or(AcoreError::NoMemory)?;
+        let idx = offset / PAGE_SIZE;
 
         let entry = pt.get_entry(vaddr)?;
         if entry.is_present() {
-            Err(AcoreError::AlreadyExists)
+            // A present but write-protected page can only mean a copy-on-write area: a
+            // write from either the forking parent or the forked child should give it its
+            // own private frame (or just regain `WRITE` if it is not shared any more).
+            if self.cow
+                && access_flags.contains(MMUFlags::WRITE)
+                && !entry.flags().contains(MMUFlags::WRITE)
+            {
+                let paddr = pma.copy_on_write(idx)?;
+                entry.set_addr(paddr);
+                entry.set_flags(self.flags);
+                pt.flush_tlb(Some(vaddr));
+                Ok(())
+            } else {
+                Err(AcoreError::AlreadyExists)
+            }
         } else {
+            let paddr = pma.get_frame(idx, true)?.ok_or(AcoreError::NoMemory)?;
             entry.set_addr(paddr);
-            entry.set_flags(self.flags);
+            entry.set_flags(self.effective_flags());
             pt.flush_tlb(Some(vaddr));
             Ok(())
         }
     }
+
+    /// Create a copy-on-write clone of this VMA for `MemorySet::fork()`.
+    ///
+    /// If the backing `PmArea` supports COW, both `self` (the parent) and the returned
+    /// (child) VMA end up write-protecting their already-mapped pages so the first write
+    /// from either side triggers `handle_page_fault` to split the sharing; otherwise the
+    /// child simply gets a reference to the same `PmArea`.
+    pub(super) fn fork(&mut self, parent_pt: &mut impl PageTable) -> AcoreResult<Self> {
+        let child_pma = self.pma.lock().fork()?;
+        let pma = match child_pma {
+            Some(child_pma) => {
+                self.cow = true;
+                if self.flags.contains(MMUFlags::WRITE) {
+                    for vaddr in (self.start..self.end).step_by(PAGE_SIZE) {
+                        if let Ok(entry) = parent_pt.get_entry(vaddr) {
+                            if entry.is_present() {
+                                entry.set_flags(self.effective_flags());
+                            }
+                        }
+                    }
+                    parent_pt.flush_tlb(None);
+                }
+                child_pma
+            }
+            None => self.pma.clone(),
+        };
+        Ok(Self {
+            start: self.start,
+            end: self.end,
+            flags: self.flags,
+            pma,
+            name: self.name,
+            cow: self.cow,
+            pin_count: AtomicUsize::new(0),
+        })
+    }
 }
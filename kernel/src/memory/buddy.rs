@@ -0,0 +1,127 @@
+//! A buddy allocator over frame indices.
+//!
+//! Replaces `bitmap_allocator`'s linear scan for `alloc_frame_contiguous`, which under
+//! fragmentation has to walk the whole bitmap and can still come up empty even when enough
+//! frames are free, just not contiguous as one run it happens to find. Free frames are tracked
+//! as power-of-two, naturally-aligned blocks in per-order free lists: order `k` covers `2^k`
+//! frames aligned to `2^k`. Allocating `frame_count` frames rounds up to an order and either
+//! pops a free block of that order directly or splits the smallest larger block available,
+//! pushing the unused buddy halves back down to their own orders. Freeing a block computes its
+//! buddy via `idx ^ (1 << order)` and coalesces upward for as long as that buddy is also free.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Largest block order tracked: `2^MAX_ORDER` frames (1 GiB of 4 KiB frames), comfortably above
+/// any single contiguous region or allocation this kernel deals with.
+const MAX_ORDER: usize = 18;
+
+pub struct BuddyAllocator {
+    /// `free_lists[k]` holds the start index of every free, order-`k` block.
+    free_lists: [Vec<usize>; MAX_ORDER + 1],
+    /// Total free frames across every order, kept in step with `free_lists` so callers can
+    /// check `free_frames()` without summing every list. Backs `frame::check_low_water`.
+    total_free: usize,
+}
+
+impl BuddyAllocator {
+    pub const fn new() -> Self {
+        const EMPTY: Vec<usize> = Vec::new();
+        Self {
+            free_lists: [EMPTY; MAX_ORDER + 1],
+            total_free: 0,
+        }
+    }
+
+    /// Total number of free frames across every order. A diagnostic, not an allocation
+    /// guarantee: frames can still be free but unusable for a large contiguous request if
+    /// they're scattered across many small blocks.
+    pub fn free_frames(&self) -> usize {
+        self.total_free
+    }
+
+    /// Seed the free lists with every maximal aligned power-of-two block that fits inside
+    /// `range`.
+    pub fn insert(&mut self, range: Range<usize>) {
+        let mut start = range.start;
+        while start < range.end {
+            let align_order = start.trailing_zeros() as usize;
+            let mut size_order = (range.end - start).next_power_of_two().trailing_zeros() as usize;
+            if (1usize << size_order) > range.end - start {
+                size_order -= 1;
+            }
+            let order = align_order.min(size_order).min(MAX_ORDER);
+            self.free_lists[order].push(start);
+            self.total_free += 1 << order;
+            start += 1 << order;
+        }
+    }
+
+    /// Allocate a single frame.
+    pub fn alloc(&mut self) -> Option<usize> {
+        self.alloc_contiguous(1, 0).map(|(start, _)| start)
+    }
+
+    /// Allocate `frame_count` contiguous frames, aligned to `1 << align_log2`. Returns the start
+    /// index along with the order actually allocated: the buddy allocator only ever hands out
+    /// power-of-two blocks, so a non-power-of-two `frame_count` gets rounded up, and the caller
+    /// must free the same rounded-up size it got back here (see `dealloc_order`), not just
+    /// `frame_count` frames, or the leftover tail leaks forever.
+    pub fn alloc_contiguous(&mut self, frame_count: usize, align_log2: usize) -> Option<(usize, usize)> {
+        let order = frame_count
+            .next_power_of_two()
+            .trailing_zeros()
+            .max(align_log2 as u32) as usize;
+        self.alloc_order(order).map(|start| (start, order))
+    }
+
+    /// Pop a free block of exactly `order`, splitting the smallest available larger block if
+    /// none is free at this order already.
+    fn alloc_order(&mut self, order: usize) -> Option<usize> {
+        if order > MAX_ORDER {
+            return None;
+        }
+        let start = if let Some(start) = self.free_lists[order].pop() {
+            start
+        } else {
+            let higher = (order + 1..=MAX_ORDER).find(|&o| !self.free_lists[o].is_empty())?;
+            let block = self.free_lists[higher].pop().unwrap();
+            for o in (order..higher).rev() {
+                self.free_lists[o].push(block + (1 << o));
+            }
+            block
+        };
+        self.total_free -= 1 << order;
+        Some(start)
+    }
+
+    /// Free the single frame at `idx`, coalescing with its buddy for as long as possible.
+    pub fn dealloc(&mut self, idx: usize) {
+        self.dealloc_order(idx, 0);
+    }
+
+    /// Free the order-`order` block starting at `idx` (as returned by `alloc_contiguous`'s
+    /// second element), coalescing with its buddy for as long as possible. Unlike calling
+    /// `dealloc` once per frame in the block, this returns the whole block in one shot instead
+    /// of relying on by-chance adjacent coalescing to put it back together.
+    pub fn dealloc_order(&mut self, idx: usize, order: usize) {
+        let (mut idx, mut order) = (idx, order);
+        while order < MAX_ORDER {
+            let buddy = idx ^ (1 << order);
+            let list = &mut self.free_lists[order];
+            match list.iter().position(|&b| b == buddy) {
+                Some(pos) => {
+                    list.swap_remove(pos);
+                    // The buddy was counted as free on its own; it is counted again below as
+                    // part of the merged block.
+                    self.total_free -= 1 << order;
+                    idx = idx.min(buddy);
+                    order += 1;
+                }
+                None => break,
+            }
+        }
+        self.free_lists[order].push(idx);
+        self.total_free += 1 << order;
+    }
+}
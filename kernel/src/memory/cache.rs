@@ -63,3 +63,11 @@ pub fn is_cache_line_aligned(offset: usize) -> bool {
 pub fn alignup_cache_line(offset: usize) -> usize {
     (offset + CACHE_LINE_SIZE - 1) & !(CACHE_LINE_SIZE - 1)
 }
+
+/// Make writes to `[addr, addr + len)` visible before the caller hands the range off across a
+/// trust boundary (e.g. a `uaccess::UserSlice` borrow being returned to user space). RISC-V's
+/// memory model here is cache-coherent, so there is no explicit cache-line flush instruction to
+/// issue; a full fence is the equivalent ordering point.
+pub fn flush(_addr: usize, _len: usize) {
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+}
@@ -1,15 +1,28 @@
 //! Physical memory allocation.
 
-use bitmap_allocator::BitAlloc;
 use core::mem::ManuallyDrop;
 
 use spin::Mutex;
 
+use super::buddy::BuddyAllocator;
 use super::{addr::phys_to_virt, PhysAddr, PAGE_SIZE, PHYS_MEMORY_OFFSET};
-use crate::arch::memory::FrameAlloc;
 use crate::error::{AcoreError, AcoreResult};
 
-static FRAME_ALLOCATOR: Mutex<FrameAlloc> = Mutex::new(FrameAlloc::DEFAULT);
+static FRAME_ALLOCATOR: Mutex<BuddyAllocator> = Mutex::new(BuddyAllocator::new());
+
+/// Below this many free frames, `alloc_frame`/`alloc_frame_contiguous` log a warning on their
+/// way out. Purely a diagnostic heads-up for whoever's watching the log, not a reservation: nothing
+/// here refuses an allocation or holds frames back for a privileged caller.
+const LOW_WATER_FRAMES: usize = 256;
+
+/// Log once an allocation has pushed the allocator below `LOW_WATER_FRAMES`, so memory pressure
+/// shows up in the log before it turns into a `NoMemory` error somewhere down the call stack.
+fn check_low_water(ba: &BuddyAllocator) {
+    let free = ba.free_frames();
+    if free < LOW_WATER_FRAMES {
+        warn!("frame allocator is low on memory: {} frames free", free);
+    }
+}
 
 fn phys_addr_to_frame_idx(addr: PhysAddr) -> usize {
     (addr - PHYS_MEMORY_OFFSET) / PAGE_SIZE
@@ -23,26 +36,37 @@ fn frame_idx_to_phys_addr(idx: usize) -> PhysAddr {
 ///
 /// This function is unsafe because your need to deallocate manually.
 unsafe fn alloc_frame() -> Option<PhysAddr> {
-    let ret = FRAME_ALLOCATOR.lock().alloc().map(frame_idx_to_phys_addr);
+    crate::trace_call!("alloc_frame");
+    let mut ba = FRAME_ALLOCATOR.lock();
+    let ret = ba.alloc().map(frame_idx_to_phys_addr);
+    if ret.is_some() {
+        check_low_water(&ba);
+    }
     trace!("Allocate frame: {:x?}", ret);
     ret
 }
 
+/// Allocate `frame_count` contiguous frames, returning the start address along with the actual
+/// (possibly larger, rounded up to a power of two by the buddy allocator) number of frames
+/// backing the allocation -- `Frame` must free exactly that many, not `frame_count`, or the
+/// rounded-up tail leaks forever.
+///
 /// # Safety
 ///
 /// This function is unsafe because your need to deallocate manually.
-unsafe fn alloc_frame_contiguous(frame_count: usize, align_log2: usize) -> Option<PhysAddr> {
-    let ret = FRAME_ALLOCATOR
-        .lock()
-        .alloc_contiguous(frame_count, align_log2)
-        .map(frame_idx_to_phys_addr);
+unsafe fn alloc_frame_contiguous(frame_count: usize, align_log2: usize) -> Option<(PhysAddr, usize)> {
+    let mut ba = FRAME_ALLOCATOR.lock();
+    let ret = ba.alloc_contiguous(frame_count, align_log2);
+    if ret.is_some() {
+        check_low_water(&ba);
+    }
     trace!(
         "Allocate {} frames with alignment {}: {:x?}",
         frame_count,
         1 << align_log2,
         ret
     );
-    ret
+    ret.map(|(idx, order)| (frame_idx_to_phys_addr(idx), 1usize << order))
 }
 
 /// # Safety
@@ -55,16 +79,17 @@ unsafe fn dealloc_frame(target: PhysAddr) {
         .dealloc(phys_addr_to_frame_idx(target))
 }
 
+/// Free the `allocated_frame_count`-frame block `alloc_frame_contiguous` returned starting at
+/// `target` -- the rounded-up size it actually allocated, not the caller's original request.
+///
 /// # Safety
 ///
 /// This function is unsafe because the frames must have been allocated.
-unsafe fn dealloc_frame_contiguous(target: PhysAddr, frame_count: usize) {
-    trace!("Deallocate {} frames: {:x}", frame_count, target);
+unsafe fn dealloc_frame_contiguous(target: PhysAddr, allocated_frame_count: usize) {
+    trace!("Deallocate {} frames: {:x}", allocated_frame_count, target);
     let start_idx = phys_addr_to_frame_idx(target);
-    let mut ba = FRAME_ALLOCATOR.lock();
-    for i in start_idx..start_idx + frame_count {
-        ba.dealloc(i)
-    }
+    let order = allocated_frame_count.trailing_zeros() as usize;
+    FRAME_ALLOCATOR.lock().dealloc_order(start_idx, order);
 }
 
 /// Initialize the frame alloactor.
@@ -80,11 +105,22 @@ pub(super) fn init() {
     info!("frame allocator init end.");
 }
 
-/// A safe wrapper for physical frame allocation.
+/// A safe wrapper for physical frame allocation. Single-owner: `Drop` always frees it.
+///
+/// Copy-on-write sharing between `MemorySet`s (e.g. across `fork()`) is built on top of this,
+/// not inside it: `PmAreaLazy`/`PmAreaFile` wrap each `Frame` in an `Arc` so a page can be
+/// mapped into multiple page tables read-only, and `copy_on_write` checks `Arc::strong_count`
+/// to decide whether a write fault needs a fresh private copy.
 #[derive(Debug)]
 pub struct Frame {
     start_paddr: PhysAddr,
     frame_count: usize,
+    /// The actual number of frames backing this allocation, which `new_contiguous` may have
+    /// rounded up from `frame_count` to the next power of two (the buddy allocator only hands
+    /// out power-of-two blocks). Kept separate from `frame_count` so `size()`/`as_slice()`/etc.
+    /// still report exactly what the caller asked for, while `Drop` frees exactly what was
+    /// actually allocated instead of leaking the rounded-up tail.
+    allocated_frame_count: usize,
 }
 
 impl Frame {
@@ -95,6 +131,7 @@ impl Frame {
                 .map(|start_paddr| Self {
                     start_paddr,
                     frame_count: 1,
+                    allocated_frame_count: 1,
                 })
                 .ok_or(AcoreError::NoMemory)
         }
@@ -104,9 +141,10 @@ impl Frame {
     pub fn new_contiguous(frame_count: usize, align_log2: usize) -> AcoreResult<Self> {
         unsafe {
             alloc_frame_contiguous(frame_count, align_log2)
-                .map(|start_paddr| Self {
+                .map(|(start_paddr, allocated_frame_count)| Self {
                     start_paddr,
                     frame_count,
+                    allocated_frame_count,
                 })
                 .ok_or(AcoreError::NoMemory)
         }
@@ -122,6 +160,7 @@ impl Frame {
         ManuallyDrop::new(Self {
             start_paddr,
             frame_count: 1,
+            allocated_frame_count: 1,
         })
     }
 
@@ -169,10 +208,10 @@ impl Frame {
 impl Drop for Frame {
     fn drop(&mut self) {
         unsafe {
-            if self.frame_count == 1 {
+            if self.allocated_frame_count == 1 {
                 dealloc_frame(self.start_paddr)
             } else {
-                dealloc_frame_contiguous(self.start_paddr, self.frame_count)
+                dealloc_frame_contiguous(self.start_paddr, self.allocated_frame_count)
             }
         }
     }
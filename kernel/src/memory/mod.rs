@@ -4,10 +4,12 @@
 
 pub mod addr;
 pub mod areas;
+mod buddy;
 pub mod cache;
 mod frame;
 mod heap;
 mod paging;
+pub mod reclaim;
 pub mod uaccess;
 mod vmm;
 
@@ -47,7 +49,7 @@ pub fn handle_kernel_page_fault(vaddr: VirtAddr, access_flags: MMUFlags) -> Acor
         vaddr, access_flags
     );
     let th = unsafe { crate::task::current() };
-    th.vm.lock().handle_page_fault(vaddr, access_flags)
+    th.process.vm.lock().handle_page_fault(vaddr, access_flags)
 }
 
 pub fn init() {
@@ -2,7 +2,7 @@
 
 use core::mem::ManuallyDrop;
 
-use super::{PhysAddr, VirtAddr};
+use super::{PhysAddr, VirtAddr, PAGE_SIZE};
 use crate::error::AcoreResult;
 
 bitflags! {
@@ -12,6 +12,12 @@ bitflags! {
         const WRITE     = 1 << 2;
         const EXECUTE   = 1 << 3;
         const USER      = 1 << 4;
+        /// Set by the MMU when a page is read or written. Used to drive clock-style page
+        /// reclamation: a page with this bit clear hasn't been touched since the last sweep.
+        const ACCESSED  = 1 << 5;
+        /// Set by the MMU on a write. A reclaimed page with this bit set must be written back
+        /// to its `PmArea` before its frame is freed.
+        const DIRTY     = 1 << 6;
     }
 }
 
@@ -19,9 +25,17 @@ pub trait PageTableEntry {
     fn addr(&self) -> PhysAddr;
     fn flags(&self) -> MMUFlags;
     fn is_present(&self) -> bool;
+    /// Whether the MMU has set the accessed bit since it was last cleared.
+    fn accessed(&self) -> bool;
+    /// Whether the MMU has set the dirty bit since it was last cleared.
+    fn dirty(&self) -> bool;
 
     fn set_addr(&mut self, paddr: PhysAddr);
     fn set_flags(&mut self, flags: MMUFlags);
+    /// Clear the accessed bit, e.g. to give a page a second chance in `memory::reclaim::Clock`.
+    fn clear_accessed(&mut self);
+    /// Clear the dirty bit, e.g. once a reclaimed page has been written back.
+    fn clear_dirty(&mut self);
     fn clear(&mut self);
 }
 
@@ -43,8 +57,33 @@ pub trait PageTable: Sized {
     /// This function is unsafe because it switches the virtual address space.
     unsafe fn set_current_root_paddr(root_paddr: PhysAddr);
 
+    /// Flush the TLB entry for `vaddr` (or the whole TLB if `None`) on the local hart, and, if
+    /// this page table is live on any other hart (see `mark_active_hart`), shoot it down there
+    /// too via an SBI remote fence.
     fn flush_tlb(&self, vaddr: Option<VirtAddr>);
 
+    /// Like calling `flush_tlb(Some(vaddr))` once for every page in `[start, end)`, but should
+    /// issue a single coalesced remote shootdown covering the whole range instead of one per
+    /// page. Used by `MemorySet::pop`'s bulk unmap, where a per-page remote fence would
+    /// dominate the cost of tearing down a large region. The default just loops `flush_tlb`;
+    /// override it where a real range-based SBI call is available.
+    fn flush_tlb_range(&self, start: VirtAddr, end: VirtAddr) {
+        for vaddr in (start..end).step_by(PAGE_SIZE) {
+            self.flush_tlb(Some(vaddr));
+        }
+    }
+
+    /// Record that this page table has become active on `hart_id`, so `flush_tlb`/
+    /// `flush_tlb_range` know which other harts might still cache its mappings and need a
+    /// remote shootdown. Called from `set_current()`. Default no-op.
+    fn mark_active_hart(&self, _hart_id: usize) {}
+
+    /// Bitmask of harts that have (or may still have) this page table active. Always empty
+    /// unless overridden alongside `mark_active_hart`.
+    fn active_harts(&self) -> usize {
+        0
+    }
+
     fn root_paddr(&self) -> PhysAddr;
 
     fn map_kernel(&mut self);
@@ -87,5 +126,6 @@ pub trait PageTable: Sized {
             Self::set_current_root_paddr(new_root);
             self.flush_tlb(None);
         }
+        self.mark_active_hart(crate::arch::cpu::id());
     }
 }
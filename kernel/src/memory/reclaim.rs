@@ -0,0 +1,89 @@
+//! Clock (second-chance) page reclamation.
+//!
+//! Tracks resident mapped pages in a circular list and uses the accessed/dirty bits
+//! `PageTableEntry` now exposes to pick an eviction victim: a page with its accessed bit set
+//! gets the bit cleared and another lap around the clock instead of being evicted; the first
+//! page found with the bit already clear is reclaimed, written back to its `PmArea` first if
+//! its dirty bit is set. This is groundwork for demand paging under memory pressure — nothing
+//! calls into `Clock` yet.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+use spin::Mutex;
+
+use super::addr::phys_to_virt;
+use super::areas::PmArea;
+use super::paging::{PageTable, PageTableEntry};
+use super::{VirtAddr, PAGE_SIZE};
+use crate::error::{AcoreError, AcoreResult};
+
+/// One page the clock is tracking: its mapped virtual address, and the `PmArea` page it backs
+/// (needed to write it back and release the frame on eviction).
+struct ClockEntry {
+    vaddr: VirtAddr,
+    pma: Arc<Mutex<dyn PmArea>>,
+    pma_idx: usize,
+}
+
+/// A circular "clock hand" over the pages pushed onto it with `track`.
+pub struct Clock {
+    entries: VecDeque<ClockEntry>,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Start tracking `vaddr`, mapped to page index `pma_idx` of `pma`, as a reclamation
+    /// candidate.
+    pub fn track(&mut self, vaddr: VirtAddr, pma: Arc<Mutex<dyn PmArea>>, pma_idx: usize) {
+        self.entries.push_back(ClockEntry {
+            vaddr,
+            pma,
+            pma_idx,
+        });
+    }
+
+    /// Sweep the clock for one victim to evict from `pt`, writing it back first if dirty.
+    /// Returns the reclaimed virtual address. `NotFound` if nothing is tracked, or if every
+    /// tracked page turned out to already be unmapped behind our back.
+    pub fn reclaim_one<PT: PageTable>(&mut self, pt: &mut PT) -> AcoreResult<VirtAddr> {
+        // A page with its accessed bit set gets at most one extra lap before it's eligible
+        // again, so two full passes over the list are always enough to find a victim.
+        let rounds = 2 * self.entries.len();
+        for _ in 0..rounds {
+            let entry = match self.entries.pop_front() {
+                Some(entry) => entry,
+                None => return Err(AcoreError::NotFound),
+            };
+            let pte = match pt.get_entry(entry.vaddr) {
+                Ok(pte) if pte.is_present() => pte,
+                // Unmapped behind our back (e.g. by `MemorySet::pop`); drop it from the clock.
+                _ => continue,
+            };
+            if pte.accessed() {
+                pte.clear_accessed();
+                pt.flush_tlb(Some(entry.vaddr));
+                self.entries.push_back(entry);
+                continue;
+            }
+            let dirty = pte.dirty();
+            let paddr = pte.addr();
+            pt.unmap(entry.vaddr)?;
+            pt.flush_tlb(Some(entry.vaddr));
+            if dirty {
+                let page = unsafe {
+                    core::slice::from_raw_parts(phys_to_virt(paddr) as *const u8, PAGE_SIZE)
+                };
+                entry.pma.lock().write(entry.pma_idx * PAGE_SIZE, page)?;
+            }
+            entry.pma.lock().release_frame(entry.pma_idx)?;
+            return Ok(entry.vaddr);
+        }
+        Err(AcoreError::NotFound)
+    }
+}
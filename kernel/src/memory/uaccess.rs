@@ -1,25 +1,58 @@
+//! Safe access to user-space memory from a syscall handler.
+//!
+//! `handle_syscall` hands raw `usize` args straight to `Syscall`'s methods; any of them that
+//! take a pointer declare it as a `UserInPtr<T>`/`UserOutPtr<T>`/`UserInOutPtr<T>` instead of a
+//! raw pointer (`syscall::mod`'s dispatch converts via `UserPtr`'s `From<VirtAddr>`). Every
+//! read/write first rejects a null, misaligned or beyond-`USER_VIRT_ADDR_LIMIT` pointer with
+//! `UserPtr::check`, then bounce-copies through `copy_from_user`/`copy_to_user` (or zero-copies
+//! via `UserSlice`), which resolve the range against `current_vm()`'s `VmArea`s before touching
+//! memory, so a bad or unmapped user pointer surfaces as an `AcoreError` instead of a
+//! kernel-side page fault.
+
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter, Result};
 use core::marker::PhantomData;
+use core::mem::{align_of, size_of, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+
+use spin::Mutex;
 
-use super::{VirtAddr, USER_VIRT_ADDR_LIMIT};
-use crate::arch::memory::with_user_access;
+use super::cache;
+use super::{MMUFlags, MemorySet, VirtAddr, PAGE_SIZE, USER_VIRT_ADDR_LIMIT};
 use crate::error::{AcoreError, AcoreResult};
 
 fn user_access_ok(uvaddr_start: VirtAddr, size: usize) -> bool {
     size <= USER_VIRT_ADDR_LIMIT && uvaddr_start <= USER_VIRT_ADDR_LIMIT - size
 }
 
-unsafe fn copy_from_user<T>(kdst: *mut T, usrc: *const T, len: usize) -> AcoreResult {
-    // TODO: handle kernel page fault
-    with_user_access(|| kdst.copy_from_nonoverlapping(usrc, len));
-    Ok(())
+/// The address space of the thread currently running on this CPU.
+fn current_vm() -> Arc<Mutex<MemorySet>> {
+    unsafe { crate::task::current() }.process.vm.clone()
+}
+
+/// Copy `len` `T`s from user address `uaddr` into `kdst`, going through `current_vm()`'s `VmArea`s
+/// page by page (lazily allocating not-yet-present frames along the way) instead of dereferencing
+/// `uaddr` directly, so a bad or unmapped user pointer yields an `AcoreError` rather than a kernel
+/// page fault.
+unsafe fn copy_from_user<T>(kdst: *mut T, uaddr: VirtAddr, len: usize) -> AcoreResult {
+    let nbytes = len * size_of::<T>();
+    let buf = core::slice::from_raw_parts_mut(kdst as *mut u8, nbytes);
+    current_vm()
+        .lock()
+        .read(uaddr, nbytes, buf, MMUFlags::READ | MMUFlags::USER)
 }
 
-unsafe fn copy_to_user<T>(udst: *mut T, ksrc: *const T, len: usize) -> AcoreResult {
-    // TODO: handle kernel page fault
-    with_user_access(|| udst.copy_from_nonoverlapping(ksrc, len));
-    Ok(())
+/// Copy `len` `T`s from `ksrc` to user address `uaddr`, going through `current_vm()`'s `VmArea`s
+/// page by page (lazily allocating not-yet-present frames along the way) instead of dereferencing
+/// `uaddr` directly, so a bad or unmapped user pointer yields an `AcoreError` rather than a kernel
+/// page fault.
+unsafe fn copy_to_user<T>(uaddr: VirtAddr, ksrc: *const T, len: usize) -> AcoreResult {
+    let nbytes = len * size_of::<T>();
+    let buf = core::slice::from_raw_parts(ksrc as *const u8, nbytes);
+    current_vm()
+        .lock()
+        .write(uaddr, nbytes, buf, MMUFlags::WRITE | MMUFlags::USER)
 }
 
 #[repr(C)]
@@ -80,13 +113,13 @@ impl<T, P: Policy> UserPtr<T, P> {
 
     pub fn check(&self, count: usize) -> AcoreResult {
         if self.ptr.is_null() {
-            return Err(AcoreError::Fault);
+            return Err(AcoreError::InvalidArgs);
         }
-        if (self.ptr as usize) % core::mem::align_of::<T>() != 0 {
+        if (self.ptr as usize) % align_of::<T>() != 0 {
             return Err(AcoreError::InvalidArgs);
         }
-        if !user_access_ok(self.ptr as usize, core::mem::size_of::<T>() * count) {
-            return Err(AcoreError::Fault);
+        if !user_access_ok(self.ptr as usize, size_of::<T>() * count) {
+            return Err(AcoreError::OutOfRange);
         }
         Ok(())
     }
@@ -95,10 +128,10 @@ impl<T, P: Policy> UserPtr<T, P> {
 impl<T, P: Read> UserPtr<T, P> {
     pub fn read(&self) -> AcoreResult<T> {
         self.check(1)?;
+        let mut value = MaybeUninit::<T>::uninit();
         unsafe {
-            let value = core::mem::MaybeUninit::uninit().assume_init();
-            copy_from_user(&value as *const _ as *mut T, self.ptr, 1)?;
-            Ok(value)
+            copy_from_user(value.as_mut_ptr(), self.ptr as VirtAddr, 1)?;
+            Ok(value.assume_init())
         }
     }
 
@@ -117,17 +150,26 @@ impl<T, P: Read> UserPtr<T, P> {
         self.check(len)?;
         let mut ret = Vec::<T>::with_capacity(len);
         unsafe {
+            copy_from_user(ret.as_mut_ptr(), self.ptr as VirtAddr, len)?;
             ret.set_len(len);
-            copy_from_user(ret.as_mut_ptr(), self.ptr, len)?;
         }
         Ok(ret)
     }
+
+    /// Borrow `len` `T`s directly out of user memory instead of bounce-copying them through
+    /// `copy_from_user`. Read-only: unlike `borrow_mut`, the returned guard only implements
+    /// `Deref`, so a caller that only asked for read access can't silently write through it. See
+    /// `UserSlice`.
+    pub fn borrow(&self, len: usize) -> AcoreResult<UserSliceRef<'_, T>> {
+        self.check(len)?;
+        UserSlice::new(self.ptr as VirtAddr, len, MMUFlags::READ | MMUFlags::USER).map(UserSliceRef)
+    }
 }
 
 impl<T, P: Write> UserPtr<T, P> {
     pub fn write(&mut self, value: T) -> AcoreResult {
         self.check(1)?;
-        unsafe { copy_to_user(self.ptr, &value as *const T, 1)? };
+        unsafe { copy_to_user(self.ptr as VirtAddr, &value as *const T, 1)? };
         Ok(())
     }
 
@@ -143,7 +185,143 @@ impl<T, P: Write> UserPtr<T, P> {
             return Ok(());
         }
         self.check(values.len())?;
-        unsafe { copy_to_user(self.ptr, values.as_ptr(), values.len())? };
+        unsafe { copy_to_user(self.ptr as VirtAddr, values.as_ptr(), values.len())? };
         Ok(())
     }
+
+    /// Borrow `len` `T`s directly out of user memory for writing instead of bounce-copying them
+    /// through `copy_to_user`. See `UserSlice`.
+    pub fn borrow_mut(&mut self, len: usize) -> AcoreResult<UserSlice<'_, T>> {
+        self.check(len)?;
+        UserSlice::new(self.ptr as VirtAddr, len, MMUFlags::WRITE | MMUFlags::USER)
+    }
+}
+
+/// A pinned, zero-copy borrow of `len` `T`s of user memory, obtained from `UserPtr::borrow`/
+/// `borrow_mut`. Unlike `copy_from_user`/`copy_to_user`, no kernel bounce buffer is allocated:
+/// `&*this`/`&mut *this` alias the user's own physical frames directly, so the kernel and the
+/// I/O CPU can read or write them in place.
+///
+/// Borrowing pins the owning `VmArea` (see `MemorySet::borrow`) so it cannot be unmapped while
+/// the guard is alive, and only succeeds if the frames backing the range are physically
+/// contiguous — the same constraint a real DMA grant would have. `Drop` flushes the range and
+/// unpins the area, so a completion may only be posted once every outstanding guard over its
+/// buffer has gone out of scope.
+pub struct UserSlice<'a, T> {
+    vm: Arc<Mutex<MemorySet>>,
+    uaddr: VirtAddr,
+    ptr: *mut T,
+    len: usize,
+    mark: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> UserSlice<'a, T> {
+    fn new(uaddr: VirtAddr, len: usize, access_flags: MMUFlags) -> AcoreResult<Self> {
+        let vm = current_vm();
+        let ptr = vm.lock().borrow(uaddr, len * size_of::<T>(), access_flags)? as *mut T;
+        Ok(Self {
+            vm,
+            uaddr,
+            ptr,
+            len,
+            mark: PhantomData,
+        })
+    }
+}
+
+impl<'a, T> Deref for UserSlice<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<'a, T> DerefMut for UserSlice<'a, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<'a, T> Drop for UserSlice<'a, T> {
+    fn drop(&mut self) {
+        cache::flush(self.ptr as usize, self.len * size_of::<T>());
+        self.vm.lock().unpin(self.uaddr);
+    }
+}
+
+/// A read-only `UserSlice`, returned by `UserPtr::borrow`: wraps the same pinned, zero-copy
+/// borrow but only implements `Deref`, not `DerefMut`, so a caller that only asked for read
+/// access can't write through it the way it could through a plain `UserSlice`.
+pub struct UserSliceRef<'a, T>(UserSlice<'a, T>);
+
+impl<'a, T> Deref for UserSliceRef<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+/// A validated user-space byte buffer usable for both reading and writing, for syscalls (e.g.
+/// `read`/`write` against a `GenericFile`) that just need a `(ptr, len)` pair rather than the
+/// `Read`/`Write`-policy split of `UserPtr`.
+#[derive(Debug)]
+pub struct UserInOutSlice {
+    ptr: VirtAddr,
+    len: usize,
+}
+
+impl UserInOutSlice {
+    pub fn new(ptr: VirtAddr, len: usize) -> AcoreResult<Self> {
+        if !user_access_ok(ptr, len) {
+            return Err(AcoreError::OutOfRange);
+        }
+        Ok(Self { ptr, len })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy the whole buffer from user space into `buf`, which must be exactly `self.len()`.
+    pub fn read_buf(&self, buf: &mut [u8]) -> AcoreResult {
+        debug_assert_eq!(buf.len(), self.len);
+        unsafe { copy_from_user(buf.as_mut_ptr(), self.ptr, buf.len()) }
+    }
+
+    /// Copy `buf`, which must be exactly `self.len()`, to user space.
+    pub fn write_buf(&self, buf: &[u8]) -> AcoreResult {
+        debug_assert_eq!(buf.len(), self.len);
+        unsafe { copy_to_user(self.ptr, buf.as_ptr(), buf.len()) }
+    }
+
+    /// Read a NUL-terminated string of up to `self.len()` bytes, one page at a time so an
+    /// unterminated buffer doesn't force copying more than necessary. Returns `OutOfRange` if
+    /// no NUL byte is found within `self.len()` bytes.
+    pub fn read_cstr(&self) -> AcoreResult<Vec<u8>> {
+        let mut result = Vec::new();
+        let mut addr = self.ptr;
+        let end = self.ptr + self.len;
+        while addr < end {
+            let chunk_len = (PAGE_SIZE - addr % PAGE_SIZE).min(end - addr);
+            let mut chunk = vec![0u8; chunk_len];
+            unsafe { copy_from_user(chunk.as_mut_ptr(), addr, chunk_len)? };
+            match chunk.iter().position(|&b| b == 0) {
+                Some(nul) => {
+                    result.extend_from_slice(&chunk[..nul]);
+                    return Ok(result);
+                }
+                None => {
+                    result.extend_from_slice(&chunk);
+                    addr += chunk_len;
+                }
+            }
+        }
+        Err(AcoreError::OutOfRange)
+    }
 }
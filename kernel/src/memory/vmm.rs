@@ -1,14 +1,15 @@
 //! Virtual memory management.
 
-use alloc::collections::{btree_map::Entry, BTreeMap};
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::fmt::{Debug, Formatter, Result};
 
 use spin::Mutex;
 
-use super::addr::{align_down, align_up, virt_to_phys, VirtAddr};
+use super::addr::{align_down, align_up, phys_to_virt, virt_to_phys, VirtAddr};
 use super::areas::VmArea;
-use super::paging::{MMUFlags, PageTable};
+use super::paging::{MMUFlags, PageTable, PageTableEntry};
 use super::{KERNEL_STACK, PAGE_SIZE, USER_VIRT_ADDR_LIMIT};
 use crate::arch::memory::ArchPageTable;
 use crate::error::{AcoreError, AcoreResult};
@@ -18,6 +19,10 @@ pub struct MemorySet<PT: PageTable = ArchPageTable> {
     areas: BTreeMap<usize, VmArea>,
     pt: PT,
     is_user: bool,
+    /// Ceiling on `mapped_bytes`, from `Resource::AddressSpace`'s soft limit (`RLIMIT_AS`).
+    /// Defaults to `USER_VIRT_ADDR_LIMIT` until `Process::new` narrows it via `set_as_limit`.
+    as_limit: usize,
+    mapped_bytes: usize,
 }
 
 impl<PT: PageTable> MemorySet<PT> {
@@ -26,6 +31,8 @@ impl<PT: PageTable> MemorySet<PT> {
             areas: BTreeMap::new(),
             pt: PT::new(),
             is_user: false,
+            as_limit: USER_VIRT_ADDR_LIMIT,
+            mapped_bytes: 0,
         }
     }
 
@@ -36,9 +43,17 @@ impl<PT: PageTable> MemorySet<PT> {
             areas: BTreeMap::new(),
             pt,
             is_user: true,
+            as_limit: USER_VIRT_ADDR_LIMIT,
+            mapped_bytes: 0,
         }
     }
 
+    /// Narrow this address space's mapping ceiling to `limit` bytes, from the owning process's
+    /// `Resource::AddressSpace` soft limit. Does not retroactively shrink already-mapped areas.
+    pub fn set_as_limit(&mut self, limit: usize) {
+        self.as_limit = limit;
+    }
+
     /// Find a free area with hint address `addr_hint` and length `len`.
     /// Return the start address of found free area.
     /// Used for mmap.
@@ -77,12 +92,26 @@ impl<PT: PageTable> MemorySet<PT> {
             warn!("VMA overlap: {:#x?}\n{:#x?}", vma, self);
             return Err(AcoreError::InvalidArgs);
         }
+        let size = vma.end - vma.start;
+        if self.mapped_bytes + size > self.as_limit {
+            warn!(
+                "address space limit exceeded: {:#x} + {:#x} > {:#x}",
+                self.mapped_bytes, size, self.as_limit
+            );
+            return Err(AcoreError::NoMemory);
+        }
         vma.map_area(&mut self.pt)?;
+        self.mapped_bytes += size;
         self.areas.insert(vma.start, vma);
         Ok(())
     }
 
-    /// Remove the area `[start_addr, end_addr)` from `MemorySet`.
+    /// Remove the region `[start, end)` from `MemorySet`, which may cover any number of whole
+    /// or partial `VmArea`s: an area entirely inside the region is dropped outright, one that
+    /// straddles a boundary is trimmed from the head or tail, and one that contains the whole
+    /// region in its middle is split in two (see `VmArea::split_at`). Gaps already unmapped
+    /// within `[start, end)` are simply skipped, so a single call can tear down a region
+    /// spanning several neighbouring areas at once.
     pub fn pop(&mut self, start: VirtAddr, end: VirtAddr) -> AcoreResult {
         if start >= end {
             warn!("invalid memory region: [{:#x?}, {:#x?})", start, end);
@@ -90,30 +119,132 @@ impl<PT: PageTable> MemorySet<PT> {
         }
         let start = align_down(start);
         let end = align_up(end);
-        if let Entry::Occupied(e) = self.areas.entry(start) {
-            if e.get().end == end {
-                e.get().unmap_area(&mut self.pt)?;
-                e.remove();
-                return Ok(());
-            }
-        }
         if self.test_free_area(start, end) {
             warn!(
                 "no matched VMA found for memory region: [{:#x?}, {:#x?})",
                 start, end
             );
-            Err(AcoreError::InvalidArgs)
-        } else {
+            return Err(AcoreError::InvalidArgs);
+        }
+
+        let keys: Vec<usize> = self
+            .areas
+            .iter()
+            .filter(|(_, area)| area.is_overlap_with(start, end))
+            .map(|(&k, _)| k)
+            .collect();
+        if keys.iter().any(|k| self.areas[k].is_pinned()) {
+            warn!("area in [{:#x?}, {:#x?}) is borrowed, cannot unmap", start, end);
+            return Err(AcoreError::BadState);
+        }
+        // A partial unmap needs `split_at` to carve the surviving head/tail back out, which
+        // refuses an area whose `PmArea` is shared with another `MemorySet` (truncating its frame
+        // vector would corrupt the other side). Check that for every area up front, before any
+        // page-table entry is touched, so a rejected call leaves the whole region as it was.
+        if keys.iter().any(|k| {
+            let area = &self.areas[k];
+            (start > area.start || end < area.end) && Arc::strong_count(&area.pma) > 1
+        }) {
+            warn!("cannot partially unmap a VMA whose PmArea is shared: [{:#x?}, {:#x?})", start, end);
+            return Err(AcoreError::NotSupported);
+        }
+
+        let mut freed = 0;
+        for key in keys {
+            let mut area = self.areas.remove(&key).unwrap();
+            let istart = start.max(area.start);
+            let iend = end.min(area.end);
+
+            if istart == area.start && iend == area.end {
+                freed += iend - istart;
+                area.unmap_area(&mut self.pt)?;
+                continue;
+            }
+
+            freed += iend - istart;
+            area.unmap_range(&mut self.pt, istart, iend)?;
+            let mut survivors = Vec::with_capacity(2);
+            if istart > area.start {
+                let tail = area.split_at(istart)?;
+                survivors.push(area);
+                area = tail;
+            }
+            if iend < area.end {
+                let tail = area.split_at(iend)?;
+                survivors.push(tail);
+            }
+            for survivor in survivors {
+                self.areas.insert(survivor.start, survivor);
+            }
+        }
+        self.mapped_bytes -= freed;
+        Ok(())
+    }
+
+    /// Change the MMU flags of every page in `[start, end)`, which may span (or partially
+    /// overlap) any number of `VmArea`s. Each overlapping area gets its covered page table
+    /// entries re-protected via `PageTable::protect` and a single coalesced `flush_tlb_range`
+    /// over the whole region; `VmArea::flags` itself is only updated when the request covers an
+    /// area in full; a protect that only clips part of an area leaves its stored flags alone
+    /// (matching what's left unprotected), the same way a real `mprotect` would need to split
+    /// the area to stay precise, which isn't implemented here.
+    pub fn protect(&mut self, start: VirtAddr, end: VirtAddr, flags: MMUFlags) -> AcoreResult {
+        if start >= end {
+            warn!("invalid memory region: [{:#x?}, {:#x?})", start, end);
+            return Err(AcoreError::InvalidArgs);
+        }
+        let start = align_down(start);
+        let end = align_up(end);
+        if self.test_free_area(start, end) {
             warn!(
-                "partially unmap memory region [{:#x?}, {:#x?}) is not supported",
+                "no matched VMA found for memory region: [{:#x?}, {:#x?})",
                 start, end
             );
-            Err(AcoreError::NotSupported)
+            return Err(AcoreError::InvalidArgs);
         }
+
+        for area in self.areas.values_mut() {
+            if !area.is_overlap_with(start, end) {
+                continue;
+            }
+            let istart = start.max(area.start);
+            let iend = end.min(area.end);
+            for vaddr in (istart..iend).step_by(PAGE_SIZE) {
+                if self.pt.get_entry(vaddr).map(|e| e.is_present()).unwrap_or(false) {
+                    self.pt.protect(vaddr, flags)?;
+                }
+            }
+            if istart == area.start && iend == area.end {
+                area.flags = flags;
+            }
+        }
+        self.pt.flush_tlb_range(start, end);
+        Ok(())
+    }
+
+    /// Create a child address space that shares physical frames with `self` in a
+    /// copy-on-write manner, like Unix `fork()`. Areas backed by a COW-capable `PmArea`
+    /// (`PmAreaLazy` and `PmAreaFile`) are write-protected in both address spaces; the first
+    /// write after the fork triggers `handle_page_fault` to give the writer a private copy.
+    /// Areas backed by a PMA with no notion of private frames (e.g. `PmAreaFixed`) are simply
+    /// shared as-is between parent and child (see `PmArea::fork`'s default `Ok(None)`).
+    pub fn fork(&mut self) -> AcoreResult<Self> {
+        let mut child = if self.is_user {
+            Self::new_user()
+        } else {
+            Self::new_kernel()
+        };
+        child.as_limit = self.as_limit;
+        for area in self.areas.values_mut() {
+            let child_area = area.fork(&mut self.pt)?;
+            child.push(child_area)?;
+        }
+        Ok(child)
     }
 
     /// Handle page fault.
     pub fn handle_page_fault(&mut self, vaddr: VirtAddr, access_flags: MMUFlags) -> AcoreResult {
+        crate::trace_call!("MemorySet::handle_page_fault");
         if let Some((_, area)) = self.areas.range(..=vaddr).last() {
             if area.contains(vaddr) {
                 return area.handle_page_fault(vaddr - area.start, access_flags, &mut self.pt);
@@ -202,6 +333,59 @@ impl<PT: PageTable> MemorySet<PT> {
             Ok(())
         })
     }
+
+    /// Resolve `[start, start + len)` to a single contiguous kernel-virtual window, pinning the
+    /// owning area so `pop()` refuses to unmap it until the matching `unpin()`. Used by
+    /// `uaccess::UserSlice` for zero-copy borrows instead of bouncing through a kernel buffer.
+    ///
+    /// Like a real DMA grant, this only works when the backing physical frames are contiguous
+    /// across the whole range: a scattered (demand-paged, not-yet-settled) backing is a hard
+    /// `NotSupported` error rather than a silent fallback to copying.
+    pub(super) fn borrow(
+        &self,
+        start: VirtAddr,
+        len: usize,
+        access_flags: MMUFlags,
+    ) -> AcoreResult<*mut u8> {
+        let (_, area) = self.areas.range(..=start).last().ok_or(AcoreError::Fault)?;
+        if !area.contains(start) || len > area.end - start {
+            return Err(AcoreError::Fault);
+        }
+        if !area.flags.contains(access_flags) {
+            return Err(AcoreError::AccessDenied);
+        }
+
+        let first_page = align_down(start);
+        let last_page = align_down(start + len - 1);
+        let mut pma = area.pma.lock();
+        let mut expect_paddr = None;
+        let mut base_paddr = 0;
+        let mut vaddr = first_page;
+        while vaddr <= last_page {
+            let idx = (vaddr - area.start) / PAGE_SIZE;
+            let paddr = pma.get_frame(idx, true)?.ok_or(AcoreError::NoMemory)?;
+            if vaddr == first_page {
+                base_paddr = paddr;
+            } else if Some(paddr) != expect_paddr {
+                return Err(AcoreError::NotSupported);
+            }
+            expect_paddr = Some(paddr + PAGE_SIZE);
+            vaddr += PAGE_SIZE;
+        }
+        drop(pma);
+
+        area.pin();
+        Ok(phys_to_virt(base_paddr + (start - first_page)) as *mut u8)
+    }
+
+    /// Undo the pin a matching `borrow()` placed on the area containing `uaddr`.
+    pub(super) fn unpin(&self, uaddr: VirtAddr) {
+        if let Some((_, area)) = self.areas.range(..=uaddr).last() {
+            if area.contains(uaddr) {
+                area.unpin();
+            }
+        }
+    }
 }
 
 impl<PT: PageTable> Drop for MemorySet<PT> {
@@ -0,0 +1,35 @@
+//! CPU affinity masks, restricting which `PerCpu` a thread or task may run on.
+
+use crate::config::CPU_NUM;
+
+/// A bitset over `0..CPU_NUM`, like a `cpu_set_t` for `sched_setaffinity`. Backed by a `u64`, so
+/// this kernel supports at most 64 CPUs — comfortably more than `CPU_NUM` is ever configured to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AffinityMask(u64);
+
+impl AffinityMask {
+    /// Eligible to run on any configured CPU — the default for a newly created thread.
+    pub fn all() -> Self {
+        if CPU_NUM >= 64 {
+            Self(u64::MAX)
+        } else {
+            Self((1 << CPU_NUM) - 1)
+        }
+    }
+
+    /// Eligible to run on `cpu` only.
+    pub fn only(cpu: usize) -> Self {
+        Self(1 << cpu)
+    }
+
+    /// Whether `cpu` is one of the CPUs this mask permits.
+    pub fn contains(&self, cpu: usize) -> bool {
+        self.0 & (1 << cpu) != 0
+    }
+}
+
+impl Default for AffinityMask {
+    fn default() -> Self {
+        Self::all()
+    }
+}
@@ -0,0 +1,135 @@
+//! A per-CPU cooperative task queue. `spawn()`/`spawn_with_affinity()` enqueue a future,
+//! `run_until_idle()` polls everything ready to completion or its next yield point, and
+//! `steal()` lets a sibling CPU lift a migration-eligible, still-runnable task off this one
+//! (see `task::PerCpu::run_until_idle`, which wires `steal()` across all `PerCpu`s).
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use spin::Mutex;
+
+use super::affinity::AffinityMask;
+
+type BoxedFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type ReadyQueue = Arc<Mutex<VecDeque<Arc<Task>>>>;
+
+pub(crate) struct Task {
+    future: Mutex<BoxedFuture>,
+    /// CPUs this task may run on; `Executor::steal` only lifts a task whose mask still permits
+    /// the stealing CPU.
+    affinity: AffinityMask,
+}
+
+#[derive(Default)]
+pub struct Executor {
+    ready: ReadyQueue,
+    /// Number of tasks other CPUs have stolen from this executor, for diagnostics.
+    stolen: AtomicUsize,
+}
+
+impl Executor {
+    pub fn spawn(&self, future: impl Future<Output = ()> + 'static + Send) {
+        self.spawn_with_affinity(future, AffinityMask::all())
+    }
+
+    /// Like `spawn`, but restricts the task to the CPUs in `affinity` — `steal()` will skip it
+    /// for any CPU not in the mask.
+    pub fn spawn_with_affinity(
+        &self,
+        future: impl Future<Output = ()> + 'static + Send,
+        affinity: AffinityMask,
+    ) {
+        let task = Arc::new(Task {
+            future: Mutex::new(Box::pin(future)),
+            affinity,
+        });
+        self.ready.lock().push_back(task);
+    }
+
+    /// Number of tasks immediately runnable on this executor — a cheap load proxy used by
+    /// `task::spawn`'s least-loaded-CPU choice.
+    pub fn len(&self) -> usize {
+        self.ready.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of tasks other CPUs have stolen from this executor.
+    pub fn steal_count(&self) -> usize {
+        self.stolen.load(Ordering::Relaxed)
+    }
+
+    /// Lift one ready task off this executor whose affinity mask still permits `target_cpu`, for
+    /// a sibling CPU's executor to run when its own queue has gone empty. Returns `None` if no
+    /// such task is currently ready.
+    pub(crate) fn steal(&self, target_cpu: usize) -> Option<Arc<Task>> {
+        let mut ready = self.ready.lock();
+        let pos = ready.iter().position(|t| t.affinity.contains(target_cpu))?;
+        let task = ready.remove(pos);
+        drop(ready);
+        self.stolen.fetch_add(1, Ordering::Relaxed);
+        task
+    }
+
+    /// Run every ready task to completion or its next yield point, repeating as long as new
+    /// tasks keep becoming ready. Once the local queue empties, `steal` is consulted for one more
+    /// task (e.g. from a sibling CPU) before giving up; returning `None` ends the loop.
+    pub fn run_until_idle(&self, mut steal: impl FnMut() -> Option<Arc<Task>>) {
+        loop {
+            let task = match self.ready.lock().pop_front() {
+                Some(task) => task,
+                None => match steal() {
+                    Some(task) => task,
+                    None => return,
+                },
+            };
+            let waker = task_waker(self.ready.clone(), task.clone());
+            let mut cx = Context::from_waker(&waker);
+            let _ = task.future.lock().as_mut().poll(&mut cx);
+        }
+    }
+}
+
+struct WakeHandle {
+    queue: ReadyQueue,
+    task: Arc<Task>,
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_handle);
+
+fn task_waker(queue: ReadyQueue, task: Arc<Task>) -> Waker {
+    let handle = Arc::new(WakeHandle { queue, task });
+    unsafe { Waker::from_raw(raw_waker(handle)) }
+}
+
+fn raw_waker(handle: Arc<WakeHandle>) -> RawWaker {
+    RawWaker::new(Arc::into_raw(handle) as *const (), &VTABLE)
+}
+
+unsafe fn clone(ptr: *const ()) -> RawWaker {
+    let handle = Arc::from_raw(ptr as *const WakeHandle);
+    let cloned = handle.clone();
+    core::mem::forget(handle);
+    raw_waker(cloned)
+}
+
+unsafe fn wake(ptr: *const ()) {
+    let handle = Arc::from_raw(ptr as *const WakeHandle);
+    handle.queue.lock().push_back(handle.task.clone());
+}
+
+unsafe fn wake_by_ref(ptr: *const ()) {
+    let handle = &*(ptr as *const WakeHandle);
+    handle.queue.lock().push_back(handle.task.clone());
+}
+
+unsafe fn drop_handle(ptr: *const ()) {
+    drop(Arc::from_raw(ptr as *const WakeHandle));
+}
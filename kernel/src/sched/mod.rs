@@ -1,10 +1,27 @@
+use alloc::sync::Arc;
 use core::{
     future::Future,
     pin::Pin,
     task::{Context, Poll},
 };
 
+mod affinity;
+mod executor;
+mod timer;
+
+pub use affinity::AffinityMask;
 pub use executor::Executor;
+pub use timer::{on_tick, Timer};
+
+use crate::task::Thread;
+
+/// Restrict `thread` to run only on the CPUs set in `mask` (see `AffinityMask`), like
+/// `sched_setaffinity`. Takes effect the next time the thread is (re)spawned onto a `PerCpu`;
+/// `task::spawn` consults it to pick the CPU and work stealing consults it to decide whether the
+/// thread's task may migrate.
+pub fn set_affinity(thread: &Arc<Thread>, mask: AffinityMask) {
+    thread.set_affinity(mask);
+}
 
 #[derive(Default)]
 struct YieldFuture {
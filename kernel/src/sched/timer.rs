@@ -0,0 +1,183 @@
+//! Hierarchical timing wheel, driving async sleeps off the hardware tick.
+//!
+//! Classic multi-level design (cf. Linux's `timer.c` / the original Varghese & Lauck paper):
+//! `LEVELS` wheels of `SLOTS_PER_LEVEL` slots each, indexed by successive 6-bit fields of the
+//! absolute expiry tick. A timer whose deadline is `delta` ticks away is filed in the lowest
+//! level whose slot range can reach that far; each hardware tick drains level 0's current slot,
+//! and whenever that index wraps back to 0 the corresponding slot of level 1 is cascaded down
+//! into the lower levels (re-bucketed by remaining delta), recursively up the stack. That gives
+//! O(1) amortized insert and O(1) amortized work per tick regardless of how many timers are
+//! outstanding.
+//!
+//! Each slot is a plain `Vec` rather than a truly intrusive linked list: cancellation is O(n) in
+//! the slot instead of O(1), but slots stay small in practice and this avoids unsafe intrusive
+//! pointers in a kernel with no test harness to catch a subtle list bug.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use spin::Mutex;
+
+const LEVELS: usize = 5;
+const SLOT_BITS: usize = 6;
+const SLOTS_PER_LEVEL: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = (SLOTS_PER_LEVEL as u64) - 1;
+
+enum TimerEntryState {
+    Pending(Option<Waker>),
+    Cancelled,
+    Fired,
+}
+
+struct TimerEntry {
+    expiry: u64,
+    state: Mutex<TimerEntryState>,
+}
+
+impl TimerEntry {
+    /// Wake the parked future, unless it was cancelled (dropped) in the meantime.
+    fn fire(&self) {
+        let mut state = self.state.lock();
+        if let TimerEntryState::Pending(waker) = &mut *state {
+            let waker = waker.take();
+            *state = TimerEntryState::Fired;
+            drop(state);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// The level whose slot range can reach a deadline `delta` ticks away: the smallest `L` such
+/// that `delta < 64^(L + 1)`, clamped to the top level.
+fn level_for(delta: u64) -> usize {
+    let mut level = 0;
+    while level + 1 < LEVELS && delta >> (SLOT_BITS * (level + 1)) != 0 {
+        level += 1;
+    }
+    level
+}
+
+struct WheelState {
+    /// `levels[l][s]` holds the timers filed in level `l`, slot `s`.
+    levels: Vec<Vec<Vec<Arc<TimerEntry>>>>,
+    current_tick: u64,
+}
+
+impl WheelState {
+    fn new() -> Self {
+        Self {
+            levels: vec![vec![Vec::new(); SLOTS_PER_LEVEL]; LEVELS],
+            current_tick: 0,
+        }
+    }
+
+    fn insert(&mut self, entry: Arc<TimerEntry>) {
+        let delta = entry.expiry.saturating_sub(self.current_tick);
+        let level = level_for(delta);
+        let slot = ((entry.expiry >> (SLOT_BITS * level)) & SLOT_MASK) as usize;
+        self.levels[level][slot].push(entry);
+    }
+
+    /// Advance by one tick, draining and returning every timer that has now expired.
+    fn advance(&mut self) -> Vec<Arc<TimerEntry>> {
+        self.current_tick += 1;
+        let mut fired = Vec::new();
+        let slot0 = (self.current_tick & SLOT_MASK) as usize;
+        fired.append(&mut self.levels[0][slot0]);
+        if slot0 == 0 {
+            self.cascade(1, &mut fired);
+        }
+        fired
+    }
+
+    /// Drain level `level`'s current slot, re-filing each entry into a lower level by its
+    /// remaining delta (or straight into `fired` if it turns out to be due already), and
+    /// recurse into the next level up if this slot's index also wrapped to 0.
+    fn cascade(&mut self, level: usize, fired: &mut Vec<Arc<TimerEntry>>) {
+        if level >= LEVELS {
+            return;
+        }
+        let slot = ((self.current_tick >> (SLOT_BITS * level)) & SLOT_MASK) as usize;
+        let entries = core::mem::take(&mut self.levels[level][slot]);
+        for entry in entries {
+            if entry.expiry <= self.current_tick {
+                fired.push(entry);
+            } else {
+                self.insert(entry);
+            }
+        }
+        if slot == 0 {
+            self.cascade(level + 1, fired);
+        }
+    }
+}
+
+lazy_static! {
+    static ref WHEEL: Mutex<WheelState> = Mutex::new(WheelState::new());
+}
+
+/// Advance the timing wheel by one tick and wake everything that just expired. Called from
+/// `arch::timer::tick()` on every hardware timer interrupt.
+pub fn on_tick() {
+    let fired = WHEEL.lock().advance();
+    for entry in fired {
+        entry.fire();
+    }
+}
+
+struct SleepFuture {
+    entry: Arc<TimerEntry>,
+    inserted: bool,
+}
+
+impl Future for SleepFuture {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.entry.state.lock();
+        if let TimerEntryState::Fired = &*state {
+            return Poll::Ready(());
+        }
+        *state = TimerEntryState::Pending(Some(cx.waker().clone()));
+        drop(state);
+        if !self.inserted {
+            self.inserted = true;
+            WHEEL.lock().insert(self.entry.clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for SleepFuture {
+    fn drop(&mut self) {
+        let mut state = self.entry.state.lock();
+        if !matches!(&*state, TimerEntryState::Fired) {
+            *state = TimerEntryState::Cancelled;
+        }
+    }
+}
+
+/// A namespace for timing-wheel sleeps, analogous to `sched::yield_now` but for "come back after
+/// N ticks" instead of "come back next poll".
+pub struct Timer;
+
+impl Timer {
+    /// Resolve once at least `ticks` hardware timer quanta have elapsed.
+    pub fn after(ticks: u64) -> impl Future<Output = ()> {
+        let expiry = WHEEL.lock().current_tick + ticks.max(1);
+        SleepFuture {
+            entry: Arc::new(TimerEntry {
+                expiry,
+                state: Mutex::new(TimerEntryState::Pending(None)),
+            }),
+            inserted: false,
+        }
+    }
+}
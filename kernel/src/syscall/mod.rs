@@ -1,12 +1,54 @@
+use alloc::boxed::Box;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::convert::TryFrom;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
 
 use crate::arch::syscall_ids::SyscallType as Sys;
 use crate::asynccall::{AsyncCall, AsyncCallInfoUser};
 use crate::error::{AcoreError, AcoreResult};
-use crate::fs::File;
+use crate::memory::areas::VmArea;
 use crate::memory::uaccess::{UserInPtr, UserOutPtr};
-use crate::task::Thread;
+use crate::memory::{addr::VirtAddr, MMUFlags, USER_VIRT_ADDR_LIMIT};
+use crate::task::{
+    noop_waker, Capability, Endpoint, Message, Object, Resource, Rights, RlimitPair, Thread,
+};
+
+bitflags! {
+    /// `PROT_*` from `sys_mmap`/`sys_mprotect`'s `prot` argument.
+    struct MmapProt: usize {
+        const READ  = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC  = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// `MAP_*` from `sys_mmap`'s `flags` argument.
+    struct MmapFlags: usize {
+        const SHARED    = 1 << 0;
+        const PRIVATE   = 1 << 1;
+        const FIXED     = 1 << 4;
+        const ANONYMOUS = 1 << 5;
+    }
+}
+
+/// The register-words-only subset of `Message` that crosses the user/kernel ABI boundary via
+/// `UserInPtr`/`UserOutPtr` — `Message::payload`'s `Vec<u8>` has no fixed-size `repr(C)`
+/// representation, so `sys_cap_*` IPC only ever moves `mrs`; a payload-carrying call would need
+/// a separate shared-memory or `mmap`-backed channel, not a `UserPtr<IpcMessage>`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct IpcMessage {
+    mrs: [usize; 4],
+}
+
+/// Returned from `sys_cap_recv`/`sys_cap_call` in place of a `Reply` capability slot when the
+/// received message didn't come from `Endpoint::call` (a plain `send` has nothing to reply to) —
+/// mirrors the `LINKED_FD` sentinel in `asynccall::structs`.
+const NO_REPLY_CAP: usize = usize::MAX;
 
 pub struct Syscall<'a> {
     thread: &'a Arc<Thread>,
@@ -32,7 +74,7 @@ impl<'a> Syscall<'a> {
         };
         debug!("Syscall: {:?} => args={:x?}", sys_type, args);
 
-        let [a0, a1, a2, a3, _a4, _a5] = args;
+        let [a0, a1, a2, a3, a4, a5] = args;
         let ret = match sys_type {
             Sys::OPENAT => self.sys_openat(a0.into(), a1, a2),
             Sys::CLOSE => self.sys_close(a0),
@@ -42,6 +84,17 @@ impl<'a> Syscall<'a> {
             Sys::GETPID => self.sys_getpid(),
             Sys::EXIT => self.sys_exit(a0),
             Sys::SETUP_ASYNC_CALL => self.sys_setup_async_call(a0, a1, a2.into(), a3),
+            Sys::GETRLIMIT => self.sys_getrlimit(a0, a1.into()),
+            Sys::SETRLIMIT => self.sys_setrlimit(a0, a1.into()),
+            Sys::NANOSLEEP => self.sys_nanosleep(a0 as u64),
+            Sys::MMAP => self.sys_mmap(a0, a1, a2, a3, a4 as isize, a5),
+            Sys::MUNMAP => self.sys_munmap(a0, a1),
+            Sys::MPROTECT => self.sys_mprotect(a0, a1, a2),
+            Sys::ENDPOINT_CREATE => self.sys_endpoint_create(),
+            Sys::CAP_SEND => self.sys_cap_send(a0, a1, a2, a3, a4),
+            Sys::CAP_RECV => self.sys_cap_recv(a0, a1.into()),
+            Sys::CAP_CALL => self.sys_cap_call(a0, a1, a2, a3, a4, a5.into()),
+            Sys::CAP_REPLY => self.sys_cap_reply(a0, a1, a2, a3, a4),
             _ => {
                 warn!("syscall unimplemented: {:?}", sys_type);
                 Err(AcoreError::NotSupported)
@@ -60,27 +113,35 @@ impl<'a> Syscall<'a> {
 impl Syscall<'_> {
     fn sys_openat(&self, path: UserInPtr<u8>, count: usize, _mode: usize) -> SysResult {
         let path = unsafe { alloc::string::String::from_utf8_unchecked(path.read_array(count)?) };
-        let file = Arc::new(File::new_memory_file(path)?);
-        Ok(self.thread.shared_res.files.lock().add_file(file)?)
+        let file = crate::fs::open(&path)?;
+        Ok(self.thread.process.shared_res.files.lock().add_file(file)?)
     }
 
     fn sys_close(&self, fd: usize) -> SysResult {
-        let file = self.thread.shared_res.files.lock().get_file(fd)?;
+        let file = self.thread.process.shared_res.files.lock().get_file(fd)?;
         file.release()?;
-        self.thread.shared_res.files.lock().remove_file(fd)?;
+        self.thread.process.shared_res.files.lock().remove_file(fd)?;
         Ok(0)
     }
 
     fn sys_read(&self, fd: usize, mut base: UserOutPtr<u8>, count: usize) -> SysResult {
-        let file = self.thread.shared_res.files.lock().get_file(fd)?;
+        let file = self.thread.process.shared_res.files.lock().get_file(fd)?;
         let mut buf = vec![0u8; count];
-        let count = file.read(0, &mut buf)?;
+        // Present the usual blocking-read behavior to a synchronous syscall by retrying in a
+        // tight loop on `WouldBlock` (e.g. `Stdin`, which never blocks internally).
+        let count = loop {
+            match file.read(0, &mut buf) {
+                Ok(n) => break n,
+                Err(AcoreError::WouldBlock) => continue,
+                Err(e) => return Err(e),
+            }
+        };
         base.write_array(&buf[..count])?;
         Ok(count)
     }
 
     fn sys_write(&self, fd: usize, base: UserInPtr<u8>, count: usize) -> SysResult {
-        let file = self.thread.shared_res.files.lock().get_file(fd)?;
+        let file = self.thread.process.shared_res.files.lock().get_file(fd)?;
         let buf = base.read_array(count)?;
         file.write(0, &buf)
     }
@@ -91,7 +152,7 @@ impl Syscall<'_> {
     }
 
     fn sys_getpid(&self) -> SysResult {
-        Ok(self.thread.id)
+        Ok(self.thread.process.pid)
     }
 
     fn sys_exit(&self, code: usize) -> SysResult {
@@ -117,4 +178,319 @@ impl Syscall<'_> {
         out_info.write(res)?;
         Ok(0)
     }
+
+    fn sys_getrlimit(&self, resource: usize, mut out_limit: UserOutPtr<RlimitPair>) -> SysResult {
+        let resource = Resource::try_from(resource)?;
+        let pair = self.thread.process.shared_res.limits.get(resource);
+        out_limit.write(pair)?;
+        Ok(0)
+    }
+
+    /// Besides updating the `ResourceLimits` bookkeeping, pushes the new soft limit into the
+    /// actual enforcement point for the two resources with one: `AddressSpace` re-syncs
+    /// `MemorySet::as_limit` (checked by every `push`), and `NoFile` grows `FileStruct`'s
+    /// fd-table/allocator (checked implicitly by its fixed range). Applied before the bookkeeping
+    /// update commits, so a rejected `NoFile` shrink (`FileStruct::resize` only grows) leaves
+    /// `ResourceLimits` untouched too instead of claiming a limit that was never enforced. A pair
+    /// that `ResourceLimits::set` would refuse is rejected before either is touched.
+    fn sys_setrlimit(&self, resource: usize, limit: UserInPtr<RlimitPair>) -> SysResult {
+        let resource = Resource::try_from(resource)?;
+        let pair = limit.read()?;
+        let current = self.thread.process.shared_res.limits.get(resource);
+        if pair.hard > current.hard || pair.soft > pair.hard {
+            return Err(AcoreError::InvalidArgs);
+        }
+        match resource {
+            Resource::AddressSpace => self.thread.process.vm.lock().set_as_limit(pair.soft),
+            Resource::NoFile => self
+                .thread
+                .process
+                .shared_res
+                .files
+                .lock()
+                .resize(pair.soft)?,
+            Resource::AsyncCallEntries | Resource::KernelStack => {}
+        }
+        self.thread
+            .process
+            .shared_res
+            .limits
+            .set(resource, pair.soft, pair.hard)?;
+        Ok(0)
+    }
+
+    /// Park the calling thread for (at least) `nanos` nanoseconds. A zero or already-elapsed
+    /// duration (the latter can't actually happen for a relative sleep, but `sleep_until` and
+    /// `nanos_to_ticks` both treat it the same way as zero) completes immediately without
+    /// parking at all.
+    fn sys_nanosleep(&self, nanos: u64) -> SysResult {
+        let ticks = crate::arch::timer::nanos_to_ticks(nanos);
+        if ticks > 0 {
+            let deadline = crate::arch::timer::tick_count() + ticks;
+            self.thread.sleep_until(deadline);
+        }
+        Ok(0)
+    }
+
+    /// Map `len` bytes of either anonymous (`MAP_ANONYMOUS`) or `fd`-backed memory into the
+    /// calling process, honoring `MAP_FIXED`'s overwrite-whatever-is-there-already semantics.
+    /// Returns the base address of the new mapping.
+    fn sys_mmap(
+        &self,
+        addr_hint: usize,
+        len: usize,
+        prot: usize,
+        flags: usize,
+        fd: isize,
+        offset: usize,
+    ) -> SysResult {
+        if len == 0 {
+            return Err(AcoreError::InvalidArgs);
+        }
+        let prot = MmapProt::from_bits_truncate(prot);
+        let flags = MmapFlags::from_bits_truncate(flags);
+        let mut mmu_flags = MMUFlags::USER;
+        if prot.contains(MmapProt::READ) {
+            mmu_flags |= MMUFlags::READ;
+        }
+        if prot.contains(MmapProt::WRITE) {
+            mmu_flags |= MMUFlags::WRITE;
+        }
+        if prot.contains(MmapProt::EXEC) {
+            mmu_flags |= MMUFlags::EXECUTE;
+        }
+
+        let mut vm = self.thread.process.vm.lock();
+        let addr: VirtAddr = if flags.contains(MmapFlags::FIXED) {
+            let addr = crate::memory::addr::align_down(addr_hint);
+            if addr + len > USER_VIRT_ADDR_LIMIT {
+                return Err(AcoreError::InvalidArgs);
+            }
+            // MAP_FIXED overwrites whatever was already mapped in the range.
+            let _ = vm.pop(addr, addr + len);
+            addr
+        } else {
+            vm.find_free_area(addr_hint, len)?
+        };
+
+        let area = if flags.contains(MmapFlags::ANONYMOUS) {
+            VmArea::from_lazy_pma(addr, len, mmu_flags, "mmap")?
+        } else {
+            let file = self
+                .thread
+                .process
+                .shared_res
+                .files
+                .lock()
+                .get_file(fd as usize)?;
+            VmArea::from_file_pma(
+                addr,
+                file,
+                offset,
+                len,
+                len,
+                mmu_flags,
+                flags.contains(MmapFlags::SHARED),
+                "mmap",
+            )?
+        };
+        vm.push(area)?;
+        Ok(addr)
+    }
+
+    fn sys_munmap(&self, addr: usize, len: usize) -> SysResult {
+        if len == 0 {
+            return Err(AcoreError::InvalidArgs);
+        }
+        self.thread.process.vm.lock().pop(addr, addr + len)?;
+        Ok(0)
+    }
+
+    fn sys_mprotect(&self, addr: usize, len: usize, prot: usize) -> SysResult {
+        if len == 0 {
+            return Err(AcoreError::InvalidArgs);
+        }
+        let prot = MmapProt::from_bits_truncate(prot);
+        let mut mmu_flags = MMUFlags::USER;
+        if prot.contains(MmapProt::READ) {
+            mmu_flags |= MMUFlags::READ;
+        }
+        if prot.contains(MmapProt::WRITE) {
+            mmu_flags |= MMUFlags::WRITE;
+        }
+        if prot.contains(MmapProt::EXEC) {
+            mmu_flags |= MMUFlags::EXECUTE;
+        }
+        self.thread
+            .process
+            .vm
+            .lock()
+            .protect(addr, addr + len, mmu_flags)?;
+        Ok(0)
+    }
+
+    /// Mint a fresh `Endpoint` into the calling thread's `cap_space` with full rights, returning
+    /// its slot index.
+    fn sys_endpoint_create(&self) -> SysResult {
+        Ok(self.thread.process.cap_space.mint(Capability {
+            object: Object::Endpoint(Endpoint::new()),
+            rights: Rights::all(),
+        }))
+    }
+
+    /// Send a 4-word message through the `Endpoint` named by `cap`, single-polling the
+    /// `SendFuture` persisted in `OwnedResource::ipc_send` so this fundamentally synchronous
+    /// syscall dispatch (see `Syscall::syscall`) doesn't have to truly block: userspace observes
+    /// `WouldBlock` and retries the same syscall, each retry re-polling the very rendezvous it
+    /// already started instead of abandoning it for a fresh one built from by-then-stale
+    /// arguments.
+    fn sys_cap_send(&self, cap: usize, m0: usize, m1: usize, m2: usize, m3: usize) -> SysResult {
+        let mut slot = self.thread.owned_res.ipc_send.lock();
+        let mut fut = match slot.take() {
+            Some(fut) => fut,
+            None => {
+                let endpoint = match self
+                    .thread
+                    .process
+                    .cap_space
+                    .lookup_rights(cap, Rights::WRITE)?
+                    .object
+                {
+                    Object::Endpoint(ep) => ep,
+                    _ => return Err(AcoreError::InvalidArgs),
+                };
+                let message = Message {
+                    mrs: [m0, m1, m2, m3],
+                    payload: Vec::new(),
+                };
+                Box::pin(endpoint.send(message))
+            }
+        };
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => result.map(|()| 0),
+            Poll::Pending => {
+                *slot = Some(fut);
+                Err(AcoreError::WouldBlock)
+            }
+        }
+    }
+
+    /// Receive a message through the `Endpoint` named by `cap`, the same single-poll-and-retry
+    /// pattern as `sys_cap_send` (persisted in `OwnedResource::ipc_recv`). On success writes the
+    /// message's register words to `out_msg` and, if the sender used `Endpoint::call` rather
+    /// than plain `send`, mints a single-use `Reply` capability to answer through and returns its
+    /// slot index — otherwise returns `NO_REPLY_CAP`.
+    fn sys_cap_recv(&self, cap: usize, mut out_msg: UserOutPtr<IpcMessage>) -> SysResult {
+        let mut slot = self.thread.owned_res.ipc_recv.lock();
+        let mut fut = match slot.take() {
+            Some(fut) => fut,
+            None => {
+                let endpoint = match self
+                    .thread
+                    .process
+                    .cap_space
+                    .lookup_rights(cap, Rights::READ)?
+                    .object
+                {
+                    Object::Endpoint(ep) => ep,
+                    _ => return Err(AcoreError::InvalidArgs),
+                };
+                Box::pin(endpoint.recv())
+            }
+        };
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(Ok((message, reply))) => {
+                out_msg.write(IpcMessage { mrs: message.mrs })?;
+                let reply_cap = match reply {
+                    Some(reply) => self.thread.process.cap_space.mint(Capability {
+                        object: Object::Reply(reply),
+                        rights: Rights::WRITE,
+                    }),
+                    None => NO_REPLY_CAP,
+                };
+                Ok(reply_cap)
+            }
+            Poll::Ready(Err(e)) => Err(e),
+            Poll::Pending => {
+                *slot = Some(fut);
+                Err(AcoreError::WouldBlock)
+            }
+        }
+    }
+
+    /// Send a 4-word message through the `Endpoint` named by `cap` and block (in the same
+    /// single-poll-and-retry style, persisted in `OwnedResource::ipc_call`) until the receiver
+    /// answers through the `Reply` capability this installs alongside it, writing the reply's
+    /// register words to `out_msg`.
+    #[allow(clippy::too_many_arguments)]
+    fn sys_cap_call(
+        &self,
+        cap: usize,
+        m0: usize,
+        m1: usize,
+        m2: usize,
+        m3: usize,
+        mut out_msg: UserOutPtr<IpcMessage>,
+    ) -> SysResult {
+        let mut slot = self.thread.owned_res.ipc_call.lock();
+        let mut fut = match slot.take() {
+            Some(fut) => fut,
+            None => {
+                let endpoint = match self
+                    .thread
+                    .process
+                    .cap_space
+                    .lookup_rights(cap, Rights::WRITE)?
+                    .object
+                {
+                    Object::Endpoint(ep) => ep,
+                    _ => return Err(AcoreError::InvalidArgs),
+                };
+                let message = Message {
+                    mrs: [m0, m1, m2, m3],
+                    payload: Vec::new(),
+                };
+                Box::pin(endpoint.call(message))
+            }
+        };
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(Ok(message)) => {
+                out_msg.write(IpcMessage { mrs: message.mrs })?;
+                Ok(0)
+            }
+            Poll::Ready(Err(e)) => Err(e),
+            Poll::Pending => {
+                *slot = Some(fut);
+                Err(AcoreError::WouldBlock)
+            }
+        }
+    }
+
+    /// Consume the single-use `Reply` capability `reply_cap` (minted by `sys_cap_recv`) to
+    /// answer its caller's `Endpoint::call` directly, without routing back through another
+    /// `Endpoint`.
+    fn sys_cap_reply(
+        &self,
+        reply_cap: usize,
+        m0: usize,
+        m1: usize,
+        m2: usize,
+        m3: usize,
+    ) -> SysResult {
+        let reply = match self.thread.process.cap_space.revoke(reply_cap)?.object {
+            Object::Reply(reply) => reply,
+            _ => return Err(AcoreError::InvalidArgs),
+        };
+        reply.send(Message {
+            mrs: [m0, m1, m2, m3],
+            payload: Vec::new(),
+        });
+        Ok(0)
+    }
 }
@@ -0,0 +1,100 @@
+use alloc::{sync::Arc, vec::Vec};
+
+use spin::Mutex;
+
+use super::ipc::{Endpoint, Reply};
+use super::Thread;
+use crate::error::{AcoreError, AcoreResult};
+use crate::fs::GenericFile;
+use crate::memory::MemorySet;
+
+bitflags! {
+    /// The operations a capability permits on its target object.
+    pub struct Rights: u32 {
+        const READ  = 1 << 0;
+        const WRITE = 1 << 1;
+        /// May derive further capabilities to the same object (e.g. hand a file cap to a
+        /// server process).
+        const GRANT = 1 << 2;
+    }
+}
+
+/// A kernel object a capability may refer to. Unlike a raw fd or an `Arc` stashed in a syscall
+/// argument, the only way a thread can touch one of these is by capability slot index, resolved
+/// and rights-checked by the kernel on every operation.
+#[derive(Clone)]
+pub enum Object {
+    Tcb(Arc<Thread>),
+    Vm(Arc<Mutex<MemorySet>>),
+    File(Arc<dyn GenericFile>),
+    Endpoint(Arc<Endpoint>),
+    Reply(Arc<Reply>),
+}
+
+/// A single `CNode` slot: the object it names, plus the rights this particular capability
+/// grants over it (the same object may be reachable through multiple capabilities with
+/// different rights).
+#[derive(Clone)]
+pub struct Capability {
+    pub object: Object,
+    pub rights: Rights,
+}
+
+/// A process's capability space: a flat array of slots, each either empty or holding a
+/// `Capability`. This is the only namespace user threads may use to refer to kernel objects —
+/// slots are named by index ("capptr") rather than by a forgeable handle or raw pointer.
+pub struct CNode {
+    slots: Mutex<Vec<Option<Capability>>>,
+}
+
+impl CNode {
+    pub fn new() -> Self {
+        Self {
+            slots: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Install `cap` in the first free slot (extending the space if none is free) and return
+    /// its index.
+    pub fn mint(&self, cap: Capability) -> usize {
+        let mut slots = self.slots.lock();
+        match slots.iter().position(Option::is_none) {
+            Some(i) => {
+                slots[i] = Some(cap);
+                i
+            }
+            None => {
+                slots.push(Some(cap));
+                slots.len() - 1
+            }
+        }
+    }
+
+    /// Resolve `slot` to its capability, failing if it is empty or out of range.
+    pub fn lookup(&self, slot: usize) -> AcoreResult<Capability> {
+        self.slots
+            .lock()
+            .get(slot)
+            .and_then(Option::clone)
+            .ok_or(AcoreError::InvalidArgs)
+    }
+
+    /// Resolve `slot`, additionally checking it grants every right in `required`.
+    pub fn lookup_rights(&self, slot: usize, required: Rights) -> AcoreResult<Capability> {
+        let cap = self.lookup(slot)?;
+        if !cap.rights.contains(required) {
+            return Err(AcoreError::AccessDenied);
+        }
+        Ok(cap)
+    }
+
+    /// Remove and return the capability in `slot`, e.g. to consume a single-use `Reply` cap
+    /// after it has been used.
+    pub fn revoke(&self, slot: usize) -> AcoreResult<Capability> {
+        self.slots
+            .lock()
+            .get_mut(slot)
+            .and_then(Option::take)
+            .ok_or(AcoreError::InvalidArgs)
+    }
+}
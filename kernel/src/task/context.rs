@@ -24,6 +24,11 @@ pub trait ThreadContext: core::fmt::Debug + Send + Sync {
     /// Set thread local storage pointer
     fn set_tls(&mut self, tls: usize);
 
+    /// Clone the register state for a COW-forked child thread (see `Thread::fork`).
+    fn fork(&self) -> Self
+    where
+        Self: Sized;
+
     /// Go to user space with the context, and come back when a trap occurs.
     /// Returns the trap kind.
     ///
@@ -54,8 +59,13 @@ impl Thread {
         let res = match trap {
             TrapReason::Syscall => self.handle_syscall(ctx),
             TrapReason::PageFault(addr, access_flags) => self.handle_page_fault(addr, access_flags),
+            TrapReason::Timer => {
+                crate::arch::timer::tick();
+                Ok(())
+            }
             _ => {
                 warn!("unhandled trap from user: {:#x?}", trap);
+                crate::arch::dump_backtrace();
                 Err(AcoreError::NotSupported)
             }
         };
@@ -65,7 +75,7 @@ impl Thread {
 
     fn handle_page_fault(&self, vaddr: VirtAddr, access_flags: MMUFlags) -> AcoreResult {
         debug!("page fault @ {:#x} with access {:?}", vaddr, access_flags);
-        self.vm.lock().handle_page_fault(vaddr, access_flags)
+        self.process.vm.lock().handle_page_fault(vaddr, access_flags)
     }
 
     fn handle_syscall(&self, ctx: &mut Box<impl ThreadContext>) -> AcoreResult {
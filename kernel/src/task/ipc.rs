@@ -0,0 +1,280 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use spin::Mutex;
+
+use crate::error::{AcoreError, AcoreResult};
+
+/// A `Waker` that does nothing when woken, for driving an `Endpoint`/`Reply` future from a
+/// synchronous syscall (see `Syscall::sys_cap_send`/`sys_cap_recv`/`sys_cap_call`) one poll at a
+/// time instead of from inside an `.await`. Those syscalls persist the future itself across
+/// calls (in `OwnedResource`) rather than rebuilding it each time, so the `Waker` stored inside
+/// `EndpointState`/`ReplyState` never actually needs to trigger a re-poll: there is no executor
+/// task parked on it to wake, since the caller re-drives the same future by invoking the syscall
+/// again, the same way a non-blocking socket is re-driven by calling `read`/`write` again on
+/// `WouldBlock`.
+static NOOP_WAKER_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(|_| noop_raw_waker(), |_| {}, |_| {}, |_| {});
+
+fn noop_raw_waker() -> RawWaker {
+    RawWaker::new(core::ptr::null(), &NOOP_WAKER_VTABLE)
+}
+
+pub(crate) fn noop_waker() -> Waker {
+    unsafe { Waker::from_raw(noop_raw_waker()) }
+}
+
+/// A short IPC message: a handful of register-sized words plus an optional byte payload, both
+/// copied by value between sender and receiver.
+#[derive(Debug, Default, Clone)]
+pub struct Message {
+    pub mrs: [usize; 4],
+    pub payload: Vec<u8>,
+}
+
+enum EndpointState {
+    Idle,
+    /// A sender is parked with its message, waiting for a receiver to take it. `reply` is set
+    /// when the send is the first half of a `call`.
+    SenderWaiting {
+        message: Message,
+        reply: Option<Arc<Reply>>,
+        waker: Waker,
+    },
+    /// A receiver is parked, waiting for a sender.
+    ReceiverWaiting(Waker),
+}
+
+/// A synchronous IPC endpoint, like a seL4 `Endpoint`: `send` rendezvous-hands a message to a
+/// waiting `recv`, and `call` additionally installs a single-use [`Reply`] capability so the
+/// receiver can answer directly to the caller instead of going through another `Endpoint`.
+///
+/// Only one outstanding sender and one outstanding receiver are supported at a time — a second
+/// concurrent `send` or `recv` fails with `BadState` rather than queueing, the same simplifying
+/// tradeoff this kernel already makes for `Process::wait` (see its doc comment).
+pub struct Endpoint {
+    state: Mutex<EndpointState>,
+}
+
+impl Endpoint {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(EndpointState::Idle),
+        })
+    }
+
+    /// Send `message`, blocking until a receiver is waiting to take it.
+    pub fn send(self: &Arc<Self>, message: Message) -> impl Future<Output = AcoreResult<()>> {
+        SendFuture {
+            endpoint: self.clone(),
+            message: Some(message),
+            reply: None,
+        }
+    }
+
+    /// Send `message` and block until the receiver replies through the single-use `Reply`
+    /// capability this installs alongside it, then return the reply message.
+    pub fn call(self: &Arc<Self>, message: Message) -> impl Future<Output = AcoreResult<Message>> {
+        let reply = Reply::new();
+        CallFuture {
+            send: SendFuture {
+                endpoint: self.clone(),
+                message: Some(message),
+                reply: Some(reply.clone()),
+            },
+            reply,
+            sent: false,
+        }
+    }
+
+    /// Block until a sender is waiting, returning its message and, if it came from `call`, the
+    /// `Reply` capability to send the response back through.
+    pub fn recv(
+        self: &Arc<Self>,
+    ) -> impl Future<Output = AcoreResult<(Message, Option<Arc<Reply>>)>> {
+        RecvFuture {
+            endpoint: self.clone(),
+            registered: false,
+        }
+    }
+}
+
+struct SendFuture {
+    endpoint: Arc<Endpoint>,
+    message: Option<Message>,
+    reply: Option<Arc<Reply>>,
+}
+
+impl Future for SendFuture {
+    type Output = AcoreResult<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AcoreResult<()>> {
+        let mut state = self.endpoint.state.lock();
+        if self.message.is_none() {
+            // Already handed off to a receiver; done once it resets the slot to `Idle`.
+            return match &*state {
+                EndpointState::Idle => Poll::Ready(Ok(())),
+                _ => Poll::Pending,
+            };
+        }
+        match &*state {
+            EndpointState::ReceiverWaiting(_) => {
+                let waker = match core::mem::replace(&mut *state, EndpointState::Idle) {
+                    EndpointState::ReceiverWaiting(w) => w,
+                    _ => unreachable!(),
+                };
+                *state = EndpointState::SenderWaiting {
+                    message: self.message.take().unwrap(),
+                    reply: self.reply.take(),
+                    waker: cx.waker().clone(),
+                };
+                drop(state);
+                waker.wake();
+                Poll::Pending
+            }
+            EndpointState::Idle => {
+                *state = EndpointState::SenderWaiting {
+                    message: self.message.take().unwrap(),
+                    reply: self.reply.take(),
+                    waker: cx.waker().clone(),
+                };
+                Poll::Pending
+            }
+            EndpointState::SenderWaiting { .. } => Poll::Ready(Err(AcoreError::BadState)),
+        }
+    }
+}
+
+struct RecvFuture {
+    endpoint: Arc<Endpoint>,
+    /// Whether *this* future is the one that put the `ReceiverWaiting` entry currently in
+    /// `endpoint.state` (if any). Needed because this future is re-polled from scratch on every
+    /// persisted `sys_cap_recv` retry (see `OwnedResource::ipc_recv`): without it, `poll` can't
+    /// tell "the registration I made last time is still sitting there, keep waiting" apart from
+    /// "a second, genuinely concurrent `recv()` got there first, fail" -- both look identical
+    /// from the enum variant alone. `SendFuture` doesn't need the equivalent because it already
+    /// has such a flag in `message: Option<_>` (`None` once taken means "already registered").
+    registered: bool,
+}
+
+impl Future for RecvFuture {
+    type Output = AcoreResult<(Message, Option<Arc<Reply>>)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.endpoint.state.lock();
+        match core::mem::replace(&mut *state, EndpointState::Idle) {
+            EndpointState::SenderWaiting {
+                message,
+                reply,
+                waker,
+            } => {
+                drop(state);
+                waker.wake();
+                Poll::Ready(Ok((message, reply)))
+            }
+            EndpointState::ReceiverWaiting(_) if this.registered => {
+                // Our own still-live registration from an earlier poll of this same future --
+                // refresh the waker and keep waiting, rather than treating it as a second,
+                // concurrent receiver the way the arm below does.
+                *state = EndpointState::ReceiverWaiting(cx.waker().clone());
+                Poll::Pending
+            }
+            EndpointState::ReceiverWaiting(waker) => {
+                // A genuinely different, concurrent receiver is already parked -- put it back
+                // untouched and fail this one instead of clobbering it, matching `SendFuture`'s
+                // symmetric `SenderWaiting` arm and what `Endpoint`'s own doc comment promises.
+                *state = EndpointState::ReceiverWaiting(waker);
+                Poll::Ready(Err(AcoreError::BadState))
+            }
+            EndpointState::Idle => {
+                this.registered = true;
+                *state = EndpointState::ReceiverWaiting(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+struct CallFuture {
+    send: SendFuture,
+    reply: Arc<Reply>,
+    sent: bool,
+}
+
+impl Future for CallFuture {
+    type Output = AcoreResult<Message>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AcoreResult<Message>> {
+        let this = self.get_mut();
+        if !this.sent {
+            match Pin::new(&mut this.send).poll(cx) {
+                Poll::Ready(Ok(())) => this.sent = true,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        // The message has been handed off; now wait for the receiver to `Reply::send` back.
+        Pin::new(&mut ReplyFuture(this.reply.clone())).poll(cx).map(Ok)
+    }
+}
+
+enum ReplyState {
+    Pending(Option<Waker>),
+    Done(Message),
+}
+
+/// A single-use reply channel `Endpoint::call` installs alongside its message, and the receiver
+/// consumes with `Reply::send` to answer the caller directly.
+pub struct Reply {
+    state: Mutex<ReplyState>,
+}
+
+impl Reply {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(ReplyState::Pending(None)),
+        })
+    }
+
+    /// Fulfil the reply with `message`, waking the parked caller. A `Reply` is single-use: a
+    /// second call is a no-op, the same way replying twice to an already-answered request
+    /// would be.
+    pub fn send(&self, message: Message) {
+        let mut state = self.state.lock();
+        if let ReplyState::Pending(waker) = &mut *state {
+            let waker = waker.take();
+            *state = ReplyState::Done(message);
+            drop(state);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+struct ReplyFuture(Arc<Reply>);
+
+impl Future for ReplyFuture {
+    type Output = Message;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Message> {
+        let mut state = self.0.state.lock();
+        match &mut *state {
+            ReplyState::Done(_) => {
+                match core::mem::replace(&mut *state, ReplyState::Pending(None)) {
+                    ReplyState::Done(message) => Poll::Ready(message),
+                    _ => unreachable!(),
+                }
+            }
+            ReplyState::Pending(waker) => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
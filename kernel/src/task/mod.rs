@@ -1,4 +1,7 @@
+mod cap;
 mod context;
+mod ipc;
+mod process;
 mod resource;
 mod thread;
 
@@ -7,10 +10,14 @@ use core::future::Future;
 
 use crate::arch::cpu;
 use crate::config::CPU_NUM;
-use crate::fs::RAM_DISK;
-use crate::sched::Executor;
+use crate::sched::{self, AffinityMask, Executor};
 
+pub use cap::{CNode, Capability, Object, Rights};
 pub use context::{ThreadContext, TrapReason};
+pub(crate) use ipc::noop_waker;
+pub use ipc::{Endpoint, Message, Reply};
+pub use process::{wait, Process};
+pub use resource::{res_limit, Resource, ResourceLimits, RlimitPair};
 pub use thread::Thread;
 
 pub const MAX_CPU_NUM: usize = 256;
@@ -83,25 +90,79 @@ impl PerCpu {
         self.executor.spawn(future)
     }
 
+    /// Like `spawn`, but restricts the task to the CPUs in `affinity` so it's ineligible for
+    /// work stealing onto any CPU outside the mask.
+    pub fn spawn_with_affinity(
+        &self,
+        future: impl Future<Output = ()> + 'static + Send,
+        affinity: AffinityMask,
+    ) {
+        self.executor.spawn_with_affinity(future, affinity)
+    }
+
+    /// Number of tasks immediately runnable on this CPU's executor, consulted by `spawn`'s
+    /// least-loaded-CPU choice.
+    pub fn queue_len(&self) -> usize {
+        self.executor.len()
+    }
+
+    /// Number of tasks other CPUs have stolen from this one.
+    pub fn steal_count(&self) -> usize {
+        self.executor.steal_count()
+    }
+
     pub fn run_until_idle(&self) {
-        self.executor.run_until_idle()
+        let my_id = Self::id();
+        self.executor.run_until_idle(|| {
+            // Prefer the busiest peer rather than the first one found with anything to steal:
+            // it's both the one most likely to still have work left over after this, and the
+            // one whose owner benefits most from shedding load.
+            (0..CPU_NUM)
+                .filter(|&id| id != my_id)
+                .max_by_key(|&id| Self::from_cpu_id(id).queue_len())
+                .and_then(|id| Self::from_cpu_id(id).executor.steal(my_id))
+        })
     }
 }
 
+/// Get the thread currently running on this CPU.
+///
+/// # Safety
+///
+/// Must only be called while a thread is actually scheduled on this CPU, i.e. not during early
+/// boot before the first `spawn()`.
+pub unsafe fn current() -> Arc<Thread> {
+    PerCpu::from_current_cpu_id().thread_unwrap().clone()
+}
+
 fn spawn(thread: Arc<Thread>) {
     info!(
         "spawn {} thread {}.",
         if thread.is_user { "user" } else { "kernel" },
         thread.id
     );
-    PerCpu::from_current_cpu_id().spawn(thread::ThreadSwitchFuture::new(thread));
+    let affinity = thread.affinity();
+    let cpu_id = least_loaded_cpu(affinity);
+    PerCpu::from_cpu_id(cpu_id)
+        .spawn_with_affinity(thread::ThreadSwitchFuture::new(thread), affinity);
+}
+
+/// Pick the CPU, among those `affinity` permits, with the fewest ready tasks — the target for a
+/// freshly spawned thread. Falls back to the current CPU if `affinity` permits none (shouldn't
+/// happen for a well-formed mask, but keeps this total instead of panicking).
+fn least_loaded_cpu(affinity: AffinityMask) -> usize {
+    (0..CPU_NUM)
+        .filter(|&id| affinity.contains(id))
+        .min_by_key(|&id| PerCpu::from_cpu_id(id).queue_len())
+        .unwrap_or_else(PerCpu::id)
 }
 
 pub fn init() {
-    let init_elf = RAM_DISK.lock().lookup("init");
-    spawn(Thread::new_kernel(thread::idle()).unwrap());
-    spawn(Thread::new_user(&init_elf, vec!["arg0".into(), "arg1".into()]).unwrap());
-    spawn(Thread::new_user(&init_elf, vec!["arg2".into(), "arg3".into()]).unwrap());
+    let idle_thread = Thread::new_kernel(thread::idle()).unwrap();
+    sched::set_affinity(&idle_thread, AffinityMask::only(PerCpu::id()));
+    spawn(idle_thread);
+    spawn(Thread::new_user("/init", vec!["arg0".into(), "arg1".into()]).unwrap());
+    spawn(Thread::new_user("/init", vec!["arg2".into(), "arg3".into()]).unwrap());
     spawn(
         Thread::new_kernel(async move {
             for i in 0..20 {
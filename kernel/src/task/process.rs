@@ -0,0 +1,165 @@
+use alloc::collections::BTreeMap;
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use spin::Mutex;
+
+use super::cap::CNode;
+use super::resource::{Resource, ResourceLimits, SharedResource};
+use crate::error::{AcoreError, AcoreResult};
+use crate::memory::{MemorySet, KERNEL_MEMORY_SET};
+use crate::utils::IdAllocator;
+
+#[derive(Default)]
+struct ProcessState {
+    /// Ids of the threads currently belonging to this process (a thread group).
+    threads: Vec<usize>,
+    exit_code: usize,
+    /// Set by `thread_exited` in the same critical section that takes `waiters` and moves the
+    /// process from `PROCESS_POOL` to `ZOMBIE_PROCESSES`, so `WaitFuture::poll` can tell "this
+    /// process finished between my `ZOMBIE_PROCESSES` check and acquiring this lock" apart from
+    /// "this process just hasn't had its first thread added yet" (both look like an empty
+    /// `threads`, right after `Process::new` and before the first `add_thread`).
+    exited: bool,
+    /// Wakers of tasks parked in `wait()` for this process, woken once it becomes a zombie.
+    waiters: Vec<Waker>,
+}
+
+/// A group of threads sharing an address space and a file table, analogous to a Unix process.
+///
+/// `Thread` is still the schedulable unit; `Process` just owns the resources a thread group
+/// shares (`vm`, `shared_res`) and the bookkeeping (`pid`, member thread ids, exit code) needed
+/// to support [`wait`]. Interned in `PROCESS_POOL` while it has at least one live thread, then
+/// moved to `ZOMBIE_PROCESSES` by `thread_exited` so its exit code survives until collected.
+pub struct Process {
+    pub pid: usize,
+    pub vm: Arc<Mutex<MemorySet>>,
+    pub shared_res: Arc<SharedResource>,
+    /// The capability space threads in this process use to name `Tcb`/`Vm`/`File`/`Endpoint`/
+    /// `Reply` objects — see [`CNode`].
+    pub cap_space: CNode,
+    state: Mutex<ProcessState>,
+}
+
+lazy_static! {
+    #[repr(align(64))]
+    static ref PID_ALLOCATOR: Mutex<IdAllocator> = Mutex::new(IdAllocator::new(1..65536));
+    #[repr(align(64))]
+    static ref PROCESS_POOL: Mutex<BTreeMap<usize, Arc<Process>>> = Mutex::new(BTreeMap::new());
+    #[repr(align(64))]
+    static ref ZOMBIE_PROCESSES: Mutex<BTreeMap<usize, Arc<Process>>> = Mutex::new(BTreeMap::new());
+    /// The shared process all kernel threads belong to: it owns `KERNEL_MEMORY_SET` and never
+    /// becomes a zombie in practice, since `idle` never exits.
+    pub static ref KERNEL_PROCESS: Arc<Process> =
+        Process::new(KERNEL_MEMORY_SET.clone(), ResourceLimits::default())
+            .expect("failed to create the kernel process");
+}
+
+impl Process {
+    pub fn new(vm: Arc<Mutex<MemorySet>>, limits: ResourceLimits) -> AcoreResult<Arc<Self>> {
+        vm.lock().set_as_limit(limits.get(Resource::AddressSpace).soft);
+        let proc = Arc::new(Self {
+            pid: PID_ALLOCATOR.lock().alloc()?,
+            vm,
+            shared_res: Arc::new(SharedResource::new(limits)?),
+            cap_space: CNode::new(),
+            state: Mutex::new(ProcessState::default()),
+        });
+        PROCESS_POOL.lock().insert(proc.pid, proc.clone());
+        Ok(proc)
+    }
+
+    /// Register `tid` as a member thread. Called once from `Thread::new()`.
+    pub(super) fn add_thread(&self, tid: usize) {
+        self.state.lock().threads.push(tid);
+    }
+
+    fn exit_code(&self) -> usize {
+        self.state.lock().exit_code
+    }
+}
+
+impl Debug for Process {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_struct("Process")
+            .field("pid", &self.pid)
+            .field("threads", &self.state.lock().threads)
+            .finish()
+    }
+}
+
+/// Detach thread `tid` from `process`, recording `exit_code`. If `tid` was the process's last
+/// member thread, move it from `PROCESS_POOL` to `ZOMBIE_PROCESSES` and wake any `wait()`ers,
+/// so the exit code is not lost before it is collected. Returns whether this was the case.
+///
+/// Called from `ThreadSwitchFuture::poll` once a thread's future resolves.
+pub(super) fn thread_exited(process: &Arc<Process>, tid: usize, exit_code: usize) -> bool {
+    let mut state = process.state.lock();
+    state.threads.retain(|&id| id != tid);
+    if !state.threads.is_empty() {
+        return false;
+    }
+    state.exit_code = exit_code;
+    state.exited = true;
+    // Keep `state` locked across the PROCESS_POOL -> ZOMBIE_PROCESSES swap below. A concurrent
+    // `WaitFuture::poll` that already missed the `ZOMBIE_PROCESSES` entry and found this
+    // process still in `PROCESS_POOL` has to go through `proc.state.lock()` next, so it either
+    // gets in before `waiters` is taken here (and is woken by it) or blocks until this function
+    // is done and then sees `exited` -- never the gap in between, where it used to register a
+    // waker that had already been taken and would never be woken again.
+    let waiters = core::mem::take(&mut state.waiters);
+    PROCESS_POOL.lock().remove(&process.pid);
+    ZOMBIE_PROCESSES.lock().insert(process.pid, process.clone());
+    drop(state);
+
+    for waker in waiters {
+        waker.wake();
+    }
+    true
+}
+
+struct WaitFuture {
+    pid: usize,
+}
+
+impl Future for WaitFuture {
+    type Output = AcoreResult<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(proc) = ZOMBIE_PROCESSES.lock().remove(&self.pid) {
+            return Poll::Ready(Ok(proc.exit_code()));
+        }
+        let proc = PROCESS_POOL.lock().get(&self.pid).cloned();
+        let proc = match proc {
+            Some(proc) => proc,
+            None => return Poll::Ready(Err(AcoreError::NotFound)),
+        };
+        let mut state = proc.state.lock();
+        if state.exited {
+            // `thread_exited` finished its `PROCESS_POOL`/`ZOMBIE_PROCESSES` swap (and dropped
+            // `state`) between this poll's `ZOMBIE_PROCESSES` check above and acquiring `state`
+            // here -- the zombie entry is sitting in `ZOMBIE_PROCESSES` now, ours to collect
+            // unless another waiter removed it first.
+            let exit_code = state.exit_code;
+            drop(state);
+            return Poll::Ready(match ZOMBIE_PROCESSES.lock().remove(&self.pid) {
+                Some(_) => Ok(exit_code),
+                None => Err(AcoreError::NotFound),
+            });
+        }
+        state.waiters.push(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// Park the calling task until process `pid` becomes a zombie (all its threads have exited),
+/// then return its exit code. Only the first `wait()` to observe a given zombie collects its
+/// code; a second call on the same `pid` fails with `NotFound`, just like a second `waitpid()`.
+pub fn wait(pid: usize) -> impl Future<Output = AcoreResult<usize>> {
+    WaitFuture { pid }
+}
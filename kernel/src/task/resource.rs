@@ -1,23 +1,58 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::future::Future;
+use core::pin::Pin;
+
 use spin::Mutex;
 
+use super::ipc::{Message, Reply};
 use crate::asynccall::AsyncCallBuffer;
-use crate::error::AcoreResult;
+use crate::error::{AcoreError, AcoreResult};
 use crate::fs::FileStruct;
 
-#[derive(Default, Debug)]
+type PendingFuture<T> = Mutex<Option<Pin<Box<dyn Future<Output = T> + Send>>>>;
+
+#[derive(Default)]
 pub struct OwnedResource {
     pub async_buf: Mutex<Option<AsyncCallBuffer>>,
+    /// The CPU `AsyncCall::setup` picked to run this thread's polling coroutine on (see
+    /// `asynccall::least_loaded_io_cpu`), so the coroutine's own exit can find its way back to
+    /// decrement that CPU's resident-coroutine count.
+    pub io_cpu: Mutex<Option<usize>>,
+    /// An in-flight `Endpoint::send`/`recv`/`call` this thread's last `sys_cap_*` syscall didn't
+    /// finish in a single poll, kept so the *next* call to that same syscall re-polls the very
+    /// same future (and rendezvous with whichever peer is waiting on it) instead of abandoning
+    /// it and building a fresh one from that later call's (by-then stale) arguments. See
+    /// `Syscall::sys_cap_send`/`sys_cap_recv`/`sys_cap_call`.
+    pub(crate) ipc_send: PendingFuture<AcoreResult<()>>,
+    pub(crate) ipc_recv: PendingFuture<AcoreResult<(Message, Option<Arc<Reply>>)>>,
+    pub(crate) ipc_call: PendingFuture<AcoreResult<Message>>,
+}
+
+impl Debug for OwnedResource {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_struct("OwnedResource")
+            .field("async_buf", &self.async_buf)
+            .field("io_cpu", &self.io_cpu)
+            .field("ipc_send_pending", &self.ipc_send.lock().is_some())
+            .field("ipc_recv_pending", &self.ipc_recv.lock().is_some())
+            .field("ipc_call_pending", &self.ipc_call.lock().is_some())
+            .finish()
+    }
 }
 
 #[derive(Debug)]
 pub struct SharedResource {
     pub files: Mutex<FileStruct>,
+    pub limits: ResourceLimits,
 }
 
 impl SharedResource {
-    pub fn new() -> AcoreResult<Self> {
+    pub fn new(limits: ResourceLimits) -> AcoreResult<Self> {
         Ok(Self {
-            files: Mutex::new(FileStruct::new(res_limit::MAX_FILE_NUM)?),
+            files: Mutex::new(FileStruct::new(limits.get(Resource::NoFile).soft)?),
+            limits,
         })
     }
 }
@@ -25,4 +60,113 @@ impl SharedResource {
 pub mod res_limit {
     pub const MAX_FILE_NUM: usize = 256;
     pub const MAX_ASYNC_CALL_ENTRY_NUM: usize = 32768;
+    /// Matches `USER_VIRT_ADDR_LIMIT`: by default a task may map its entire address space.
+    pub const MAX_ADDRESS_SPACE_SIZE: usize = 0xFFFF_FFFF;
+    /// This kernel's kernel stacks are fixed-size slices of a compile-time `KERNEL_STACK` array
+    /// (see `memory::KERNEL_STACK_SIZE`), so this limit is bookkeeping only for now — there is no
+    /// code path that grows a kernel stack on request yet.
+    pub const MAX_KERNEL_STACK_SIZE: usize = 0x8000;
+}
+
+/// Which `ResourceLimits` pair to query or adjust, modeled on `getrlimit`/`setrlimit`'s
+/// `RLIMIT_*` resource ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Resource {
+    /// Max number of open file descriptors (`RLIMIT_NOFILE`).
+    NoFile = 0,
+    /// Max number of outstanding entries in an async-call ring.
+    AsyncCallEntries = 1,
+    /// Max bytes of user address space a `MemorySet` may map (`RLIMIT_AS`).
+    AddressSpace = 2,
+    /// Max bytes of kernel stack (`RLIMIT_STACK` analogue; see `res_limit::MAX_KERNEL_STACK_SIZE`).
+    KernelStack = 3,
+}
+
+const RESOURCE_COUNT: usize = 4;
+
+impl core::convert::TryFrom<usize> for Resource {
+    type Error = AcoreError;
+
+    fn try_from(id: usize) -> Result<Self, Self::Error> {
+        Ok(match id {
+            0 => Self::NoFile,
+            1 => Self::AsyncCallEntries,
+            2 => Self::AddressSpace,
+            3 => Self::KernelStack,
+            _ => return Err(AcoreError::InvalidArgs),
+        })
+    }
+}
+
+/// A soft/hard limit pair, like `struct rlimit` from `getrlimit(2)`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RlimitPair {
+    pub soft: usize,
+    pub hard: usize,
+}
+
+/// Per-process resource ceilings — open files, async-call ring entries, mapped address-space
+/// size, kernel-stack size — inherited by every thread in the process (see
+/// `SharedResource::limits`) and consulted in place of the old `res_limit` constants, so each
+/// process can be sandboxed independently instead of sharing one global maximum.
+#[derive(Debug)]
+pub struct ResourceLimits {
+    limits: Mutex<[RlimitPair; RESOURCE_COUNT]>,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        use res_limit::*;
+        Self {
+            limits: Mutex::new([
+                RlimitPair {
+                    soft: MAX_FILE_NUM,
+                    hard: MAX_FILE_NUM,
+                },
+                RlimitPair {
+                    soft: MAX_ASYNC_CALL_ENTRY_NUM,
+                    hard: MAX_ASYNC_CALL_ENTRY_NUM,
+                },
+                RlimitPair {
+                    soft: MAX_ADDRESS_SPACE_SIZE,
+                    hard: MAX_ADDRESS_SPACE_SIZE,
+                },
+                RlimitPair {
+                    soft: MAX_KERNEL_STACK_SIZE,
+                    hard: MAX_KERNEL_STACK_SIZE,
+                },
+            ]),
+        }
+    }
+}
+
+impl Clone for ResourceLimits {
+    /// Snapshot the current soft/hard pairs, for a child process (e.g. `Thread::fork`) to
+    /// inherit its parent's limits rather than starting over at the global defaults.
+    fn clone(&self) -> Self {
+        Self {
+            limits: Mutex::new(*self.limits.lock()),
+        }
+    }
+}
+
+impl ResourceLimits {
+    pub fn get(&self, resource: Resource) -> RlimitPair {
+        self.limits.lock()[resource as usize]
+    }
+
+    /// Set `resource`'s soft/hard pair. `soft` may be set anywhere up to the new `hard`, and
+    /// `hard` may be lowered but never raised above its *current* value, matching `setrlimit`'s
+    /// unprivileged semantics (aCore has no separate privileged-raise path yet).
+    pub fn set(&self, resource: Resource, soft: usize, hard: usize) -> AcoreResult {
+        let mut limits = self.limits.lock();
+        let current = &mut limits[resource as usize];
+        if hard > current.hard || soft > hard {
+            return Err(AcoreError::InvalidArgs);
+        }
+        *current = RlimitPair { soft, hard };
+        Ok(())
+    }
 }
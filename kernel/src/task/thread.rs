@@ -9,34 +9,60 @@ use core::{
 
 use spin::Mutex;
 
+use super::cap::{Capability, Object, Rights};
 use super::context::ThreadContext;
-use super::resource::{OwnedResource, SharedResource};
+use super::process::{self, Process, KERNEL_PROCESS};
+use super::resource::{OwnedResource, ResourceLimits};
 use crate::arch::context::ArchThreadContext;
 use crate::error::{AcoreError, AcoreResult};
-use crate::fs::File;
-use crate::memory::{MemorySet, KERNEL_MEMORY_SET};
-use crate::sched::yield_now;
+use crate::fs::open_path;
+use crate::memory::MemorySet;
+use crate::sched::{yield_now, AffinityMask, Timer};
 use crate::utils::{ElfLoader, IdAllocator};
 
 type ThreadFuture = dyn Future<Output = AcoreResult> + Send;
 type ThreadFuturePinned = Pin<Box<ThreadFuture>>;
 
-#[derive(Debug, Default)]
+/// Timer quanta (see `arch::timer::tick`) a thread may run for before `charge_time_slice`
+/// forces a reschedule. A few quanta rather than one: preempting on literally every tick would
+/// make `run_user`'s loop pay a `yield_now()` round trip far more often than fairness needs.
+const TIME_SLICE_TICKS: u32 = 5;
+
+#[derive(Debug)]
 struct ThreadState {
     need_sched: bool,
     exited: bool,
+    exit_code: usize,
+    /// Absolute timing-wheel tick (see `sched::Timer`) this thread is parked until, set by
+    /// `sys_nanosleep`. Checked and cleared by `run_user`'s loop before re-entering user code.
+    sleep_until: Option<u64>,
+    /// Quanta left in this thread's current time slice, charged down by `charge_time_slice` on
+    /// every timer tick while it's the running thread; reset whenever it's rescheduled.
+    ticks_left: u32,
+}
+
+impl Default for ThreadState {
+    fn default() -> Self {
+        Self {
+            need_sched: false,
+            exited: false,
+            exit_code: 0,
+            sleep_until: None,
+            ticks_left: TIME_SLICE_TICKS,
+        }
+    }
 }
 
 pub struct Thread<C: ThreadContext = ArchThreadContext> {
     pub id: usize,
     pub cpu: usize,
     pub is_user: bool,
-    pub vm: Arc<Mutex<MemorySet>>,
-    pub owned_res: Mutex<OwnedResource>,
-    pub shared_res: Arc<Mutex<SharedResource>>,
+    pub process: Arc<Process>,
+    pub owned_res: OwnedResource,
     context: Mutex<Option<Box<C>>>,
     state: Mutex<ThreadState>,
     future: Mutex<ThreadFuturePinned>,
+    affinity: Mutex<AffinityMask>,
 }
 
 lazy_static! {
@@ -49,17 +75,22 @@ lazy_static! {
 }
 
 impl Thread {
-    fn new(is_user: bool, vm: Arc<Mutex<MemorySet>>) -> AcoreResult<Arc<Self>> {
+    fn new(is_user: bool, process: Arc<Process>) -> AcoreResult<Arc<Self>> {
         let th = Arc::new(Self {
             id: TID_ALLOCATOR.lock().alloc()?,
             cpu: crate::arch::cpu::id(),
             is_user,
-            vm,
-            owned_res: Mutex::new(OwnedResource::default()),
-            shared_res: Arc::new(Mutex::new(SharedResource::default())),
+            process,
+            owned_res: OwnedResource::default(),
             context: Mutex::new(None),
             state: Mutex::new(ThreadState::default()),
             future: Mutex::new(Box::pin(async { Ok(()) })),
+            affinity: Mutex::new(AffinityMask::default()),
+        });
+        th.process.add_thread(th.id);
+        th.process.cap_space.mint(Capability {
+            object: Object::Tcb(th.clone()),
+            rights: Rights::all(),
         });
         THREAD_POOL.lock().insert(th.id, th.clone());
         Ok(th)
@@ -68,18 +99,20 @@ impl Thread {
     pub fn new_kernel(
         entry: impl Future<Output = AcoreResult> + Send + 'static,
     ) -> AcoreResult<Arc<Self>> {
-        let th = Self::new(false, KERNEL_MEMORY_SET.clone())?;
+        let th = Self::new(false, KERNEL_PROCESS.clone())?;
         *th.future.lock() = Box::pin(entry);
         debug!("new kernel thread: {:#x?}", th);
         Ok(th)
     }
 
-    pub fn new_user(file: &File, args: Vec<String>) -> AcoreResult<Arc<Self>> {
-        let loader = ElfLoader::new(file)?;
+    pub fn new_user(path: &str, args: Vec<String>) -> AcoreResult<Arc<Self>> {
+        let file = open_path(path)?.as_file()?;
+        let loader = ElfLoader::new(&file)?;
         let mut vm = MemorySet::new_user();
         let (entry_pointer, ustack_pointer) = loader.init_vm(&mut vm, args)?;
 
-        let th = Self::new(true, Arc::new(Mutex::new(vm)))?;
+        let process = Process::new(Arc::new(Mutex::new(vm)), ResourceLimits::default())?;
+        let th = Self::new(true, process)?;
         let tmp = th.clone();
         *th.future.lock() = Box::pin(async move { tmp.run_user().await });
         let ctx = ArchThreadContext::new(entry_pointer, ustack_pointer);
@@ -89,20 +122,71 @@ impl Thread {
         Ok(th)
     }
 
+    /// Create a child thread in a new process that shares this thread's address space via
+    /// copy-on-write, like a Unix `fork()`. Much cheaper than `new_user()` for spawning a new
+    /// process since it skips reloading and relinking the ELF image.
+    pub fn fork(self: &Arc<Self>) -> AcoreResult<Arc<Self>> {
+        if !self.is_user {
+            return Err(AcoreError::BadState);
+        }
+        let child_vm = self.process.vm.lock().fork()?;
+        let limits = self.process.shared_res.limits.clone();
+        let process = Process::new(Arc::new(Mutex::new(child_vm)), limits)?;
+        let th = Self::new(true, process)?;
+        let child_ctx = self
+            .context
+            .lock()
+            .as_ref()
+            .ok_or(AcoreError::BadState)?
+            .fork();
+
+        let tmp = th.clone();
+        *th.future.lock() = Box::pin(async move { tmp.run_user().await });
+        *th.context.lock() = Some(Box::new(child_ctx));
+
+        debug!("forked user thread {} -> {}: {:#x?}", self.id, th.id, th);
+        Ok(th)
+    }
+
     pub fn is_exited(&self) -> bool {
         self.state.lock().exited
     }
 
-    pub fn exit(&self, _code: usize) {
-        self.state.lock().exited = true;
-        if self.is_user {
-            self.vm.lock().clear(); // remove all user mappings
-        }
+    pub fn exit(&self, code: usize) {
+        let mut state = self.state.lock();
+        state.exited = true;
+        state.exit_code = code;
     }
 
     pub fn set_need_sched(&self) {
         self.state.lock().need_sched = true;
     }
+
+    /// Charge one timer tick against this thread's time slice, called from `arch::timer::tick`
+    /// for whichever thread is currently running on this CPU. Once the slice is exhausted,
+    /// marks the thread as needing a reschedule (see `set_need_sched`) so `run_user`'s loop
+    /// yields at the next trap boundary instead of running forever between syscalls.
+    pub fn charge_time_slice(&self) {
+        let mut state = self.state.lock();
+        state.ticks_left = state.ticks_left.saturating_sub(1);
+        if state.ticks_left == 0 {
+            state.need_sched = true;
+        }
+    }
+
+    /// Park this thread's `run_user` loop until the timing wheel reaches `deadline_tick`,
+    /// without re-entering user code in the meantime. Used by `sys_nanosleep`.
+    pub(crate) fn sleep_until(&self, deadline_tick: u64) {
+        self.state.lock().sleep_until = Some(deadline_tick);
+    }
+
+    pub fn affinity(&self) -> AffinityMask {
+        *self.affinity.lock()
+    }
+
+    pub(crate) fn set_affinity(&self, mask: AffinityMask) {
+        *self.affinity.lock() = mask;
+    }
 }
 
 impl Thread {
@@ -125,9 +209,20 @@ impl Thread {
             if state.exited {
                 break;
             }
-            if state.need_sched {
-                state.need_sched = false;
-                yield_now().await?;
+            let sleep_until = state.sleep_until.take();
+            let need_sched = core::mem::take(&mut state.need_sched);
+            if need_sched || sleep_until.is_some() {
+                state.ticks_left = TIME_SLICE_TICKS;
+            }
+            drop(state);
+
+            if let Some(deadline) = sleep_until {
+                let now = crate::arch::timer::tick_count();
+                if deadline > now {
+                    Timer::after(deadline - now).await;
+                }
+            } else if need_sched {
+                yield_now().await;
             }
         }
         Ok(())
@@ -145,9 +240,11 @@ impl<C: ThreadContext> Drop for Thread<C> {
 impl<C: ThreadContext> Debug for Thread<C> {
     fn fmt(&self, f: &mut Formatter) -> Result {
         let mut f = f.debug_struct("Thread");
-        f.field("id", &self.id).field("cpu", &self.cpu);
+        f.field("id", &self.id)
+            .field("cpu", &self.cpu)
+            .field("pid", &self.process.pid);
         if self.is_user {
-            f.field("vm", &self.vm);
+            f.field("vm", &self.process.vm);
         } else {
             f.field("vm", &format_args!("KERNEL_MEMORY_SET"));
         }
@@ -178,11 +275,16 @@ impl Future for ThreadSwitchFuture {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         unsafe {
             crate::arch::context::write_tls(self.0.tls_ptr());
-            self.0.vm.lock().activate();
+            self.0.process.vm.lock().activate();
         }
         self.0.future.lock().as_mut().poll(cx).map(|res| {
             info!("thread {} exited with {:?}.", self.0.id, res);
             THREAD_POOL.lock().remove(&self.0.id);
+            let exit_code = self.0.state.lock().exit_code;
+            let is_last_thread = process::thread_exited(&self.0.process, self.0.id, exit_code);
+            if is_last_thread && self.0.is_user {
+                self.0.process.vm.lock().clear(); // remove all user mappings
+            }
             // add to zombie thread list, it will finally drop in idle thread
             ZOMBIES.lock().push(self.0.clone());
         })
@@ -22,4 +22,10 @@ impl IdAllocator {
     pub fn dealloc(&mut self, id: usize) {
         self.inner.dealloc(id)
     }
+
+    /// Extend the allocatable range to additionally cover `old_end..new_end`, e.g. when a
+    /// process's fd-table ceiling is raised by `setrlimit` after the table was first sized.
+    pub fn grow(&mut self, old_end: usize, new_end: usize) {
+        self.inner.insert(old_end..new_end);
+    }
 }
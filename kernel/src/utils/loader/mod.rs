@@ -6,18 +6,19 @@ use core::convert::From;
 use crate::error::{AcoreError, AcoreResult};
 use crate::fs::File;
 use crate::memory::addr::{page_count, page_offset, VirtAddr};
-use crate::memory::areas::{PmArea, PmAreaLazy, VmArea};
+use crate::memory::areas::{PmArea, PmAreaFile, PmAreaLazy, VmArea};
 use crate::memory::{MMUFlags, MemorySet, PAGE_SIZE, USER_STACK_OFFSET, USER_STACK_SIZE};
 
 use spin::Mutex;
 use xmas_elf::{
     header,
-    program::{Flags, SegmentData, Type},
+    program::{Flags, Type},
     ElfFile,
 };
 
-pub struct ElfLoader<'a> {
-    elf: ElfFile<'a>,
+pub struct ElfLoader {
+    elf: ElfFile<'static>,
+    file: Arc<File>,
 }
 
 impl From<&str> for AcoreError {
@@ -27,8 +28,9 @@ impl From<&str> for AcoreError {
     }
 }
 
-impl<'a> ElfLoader<'a> {
-    pub fn new(file: &'a File) -> AcoreResult<Self> {
+impl ElfLoader {
+    pub fn new(file: &File) -> AcoreResult<Self> {
+        let file = Arc::new(file.clone());
         let elf = ElfFile::new(file.as_slice_mut())?;
 
         #[cfg(target_pointer_width = "32")]
@@ -48,7 +50,7 @@ impl<'a> ElfLoader<'a> {
             header::Machine::Other(0xF3) => {}
             _ => return Err("invalid ELF arch".into()),
         };
-        Ok(Self { elf })
+        Ok(Self { elf, file })
     }
 
     pub fn init_vm(
@@ -66,19 +68,29 @@ impl<'a> ElfLoader<'a> {
             }
 
             let pgoff = page_offset(ph.virtual_addr() as usize);
-            let page_count = page_count(ph.mem_size() as usize + pgoff);
-            let mut pma = PmAreaLazy::new(page_count)?;
-            let data = match ph.get_data(&self.elf).unwrap() {
-                SegmentData::Undefined(data) => data,
-                _ => return Err(AcoreError::InvalidArgs),
+            let file_size = ph.file_size() as usize;
+            let total_size = ph.mem_size() as usize + pgoff;
+
+            // Demand-page the segment instead of eagerly copying it: file-backed bytes are
+            // read from `file` lazily on first fault, with anything past `file_size` (e.g.
+            // the `.bss` tail) zero-filled. A segment with no file data at all (a pure
+            // anonymous `.bss` program header) just gets a `PmAreaLazy`.
+            let pma: Arc<Mutex<dyn PmArea>> = if file_size > 0 {
+                Arc::new(Mutex::new(PmAreaFile::new(
+                    self.file.clone(),
+                    ph.offset() as usize - pgoff,
+                    file_size + pgoff,
+                    total_size,
+                )?))
+            } else {
+                Arc::new(Mutex::new(PmAreaLazy::new(page_count(total_size))?))
             };
-            pma.write(pgoff, data)?;
 
             let seg = VmArea::new(
                 ph.virtual_addr() as VirtAddr,
                 (ph.virtual_addr() + ph.mem_size()) as VirtAddr,
                 ph.flags().into(),
-                Arc::new(Mutex::new(pma)),
+                pma,
                 "elf_segment",
             )?;
             vm.push(seg)?;